@@ -11,7 +11,7 @@ while read url; do
     filename=$(basename "$url")
     curl -s "$url" -o "$filename"
     # Replace URL in CSS
-    sed -i "s|$url|/fonts/$filename|g" inter.css
+    sed -i "s|$url|$filename|g" inter.css
 done < inter_urls.txt
 
 # Download Material Icons
@@ -22,7 +22,7 @@ grep -o 'https://[^)]*\.woff2' icons.css | sort -u > icon_urls.txt
 while read url; do
     filename="material-icons.woff2" # there's usually just one
     curl -s "$url" -o "$filename"
-    sed -i "s|$url|/fonts/$filename|g" icons.css
+    sed -i "s|$url|$filename|g" icons.css
 done < icon_urls.txt
 
 # Clean up
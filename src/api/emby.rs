@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
-use crate::api::ServiceStatus;
+use async_trait::async_trait;
+use crate::api::{Capability, ConfigField, Probe, ProbeError, ServiceClient, ServiceDescriptor};
 
 #[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,64 +25,67 @@ struct NowPlayingItem {
     pub name: String,
 }
 
-pub async fn get_status(client: &Client, url: &str, api_key: &str) -> ServiceStatus {
-    let endpoint = format!("{}/Sessions?api_key={}", url, api_key);
-    match client.get(&endpoint).send().await {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                match resp.json::<Vec<EmbySession>>().await {
-                    Ok(sessions) => {
-                        let active_sessions: Vec<_> = sessions.into_iter()
-                            .filter(|s| s.now_playing_item.is_some())
-                            .collect();
-                        
-                        let active_count = active_sessions.len();
-                        let names: Vec<String> = active_sessions.iter()
-                            .map(|s| format!("{} ({})", 
-                                s.now_playing_item.as_ref().map(|i| i.name.as_str()).unwrap_or("Unknown"),
-                                s.user_name.as_deref().unwrap_or("Unknown")
-                            ))
-                            .collect();
+pub const SERVICE: ServiceDescriptor = ServiceDescriptor {
+    id: "emby",
+    fields: &[ConfigField::url("emby_url"), ConfigField::secret("emby_key")],
+    multi_instance: false,
+    connect: |client, settings| Box::new(Emby {
+        client: client.clone(),
+        url: settings.get("emby_url").to_string(),
+        api_key: settings.get("emby_key").to_string(),
+    }),
+};
 
-                        ServiceStatus {
-                            name: "Emby".to_string(),
-                            active: true,
-                            message: format!("{} active session(s)", active_count),
-                            url: url.to_string(),
-                            version: None,
-                            extras: Some(serde_json::json!({
-                                "active_sessions": active_count,
-                                "sessions": names
-                            })),
-                        }
-                    }
-                    Err(e) => ServiceStatus {
-                        name: "Emby".to_string(),
-                        active: true,
-                        message: format!("Parse Error: {}", e),
-                        url: url.to_string(),
-                        version: None,
-                        extras: None,
-                    },
-                }
-            } else {
-                ServiceStatus {
-                    name: "Emby".to_string(),
-                    active: false,
-                    message: format!("HTTP {}", resp.status()),
-                    url: url.to_string(),
-                    version: None,
-                    extras: None,
-                }
-            }
+pub struct Emby {
+    client: Client,
+    url: String,
+    api_key: String,
+}
+
+#[async_trait]
+impl ServiceClient for Emby {
+    fn id(&self) -> &str {
+        SERVICE.id
+    }
+
+    fn name(&self) -> &'static str {
+        "Emby"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Extras]
+    }
+
+    async fn probe(&self) -> Result<Probe, ProbeError> {
+        let endpoint = format!("{}/Sessions?api_key={}", self.url, self.api_key);
+        let resp = self.client.get(&endpoint).send().await?;
+        if !resp.status().is_success() {
+            return Err(ProbeError::status(resp.status()));
         }
-        Err(e) => ServiceStatus {
-            name: "Emby".to_string(),
-            active: false,
-            message: e.to_string(),
-            url: url.to_string(),
+        let sessions = resp.json::<Vec<EmbySession>>().await?;
+        let active_sessions: Vec<_> = sessions.into_iter()
+            .filter(|s| s.now_playing_item.is_some())
+            .collect();
+
+        let active_count = active_sessions.len();
+        let names: Vec<String> = active_sessions.iter()
+            .map(|s| format!("{} ({})",
+                s.now_playing_item.as_ref().map(|i| i.name.as_str()).unwrap_or("Unknown"),
+                s.user_name.as_deref().unwrap_or("Unknown")
+            ))
+            .collect();
+
+        Ok(Probe {
+            message: Some(format!("{} active session(s)", active_count)),
             version: None,
-            extras: None,
-        },
+            extras: Some(serde_json::json!({
+                "active_sessions": active_count,
+                "sessions": names
+            })),
+        })
     }
 }
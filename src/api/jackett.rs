@@ -1,68 +1,86 @@
 use reqwest::Client;
-use crate::api::ServiceStatus;
+use async_trait::async_trait;
+use crate::api::{Capability, ConfigField, Probe, ProbeError, ServiceClient, ServiceDescriptor};
 
-pub async fn get_status(client: &Client, url: &str, api_key: &str) -> ServiceStatus {
-    let base = url.trim_end_matches('/');
+pub const SERVICE: ServiceDescriptor = ServiceDescriptor {
+    id: "jackett",
+    fields: &[ConfigField::url("jackett_url"), ConfigField::secret("jackett_key")],
+    multi_instance: false,
+    connect: |client, settings| Box::new(Jackett {
+        client: client.clone(),
+        url: settings.get("jackett_url").to_string(),
+        api_key: settings.get("jackett_key").to_string(),
+    }),
+};
+
+pub struct Jackett {
+    client: Client,
+    url: String,
+    api_key: String,
+}
+
+#[async_trait]
+impl ServiceClient for Jackett {
+    fn id(&self) -> &str {
+        SERVICE.id
+    }
+
+    fn name(&self) -> &'static str {
+        "Jackett"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Extras]
+    }
+
+    async fn probe(&self) -> Result<Probe, ProbeError> {
+        let base = self.url.trim_end_matches('/');
 
-    // The /api/v2.0/indexers endpoint requires browser cookies.
-    // The correct machine-to-machine health check is the search endpoint:
-    let health_endpoint = format!("{}/api/v2.0/indexers/all/results?apikey={}&t=search&q=", base, api_key);
-
-    match client.get(&health_endpoint).send().await {
-        Ok(resp) if resp.status().is_success() => {
-            // Use the Torznab ?t=indexers endpoint — accepts apikey without cookies, returns XML
-            let torznab_endpoint = format!(
-                "{}/api/v2.0/indexers/all/results/torznab/api?apikey={}&t=indexers",
-                base, api_key
-            );
-            let (total, failed_count) = if let Ok(r) = client.get(&torznab_endpoint).send().await {
-                if let Ok(xml) = r.text().await {
-                    // Count only indexers with configured="true" in their opening tag
-                    let total = count_configured_indexers(&xml);
-                    (total, 0i64)
-                } else {
-                    (0, 0)
-                }
+        // The /api/v2.0/indexers endpoint requires browser cookies.
+        // The correct machine-to-machine health check is the search endpoint:
+        let health_endpoint = format!("{}/api/v2.0/indexers/all/results?apikey={}&t=search&q=", base, self.api_key);
+
+        let resp = self.client.get(&health_endpoint).send().await
+            .map_err(|e| ProbeError::Connection(format!("Connection error: {}", e)))?;
+        if !resp.status().is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(ProbeError::Http(format!("HTTP — {}", body.chars().take(80).collect::<String>())));
+        }
+        Ok(Probe::default())
+    }
+
+    async fn extras(&self) -> Option<serde_json::Value> {
+        let base = self.url.trim_end_matches('/');
+
+        // Use the Torznab ?t=indexers endpoint — accepts apikey without cookies, returns XML
+        let torznab_endpoint = format!(
+            "{}/api/v2.0/indexers/all/results/torznab/api?apikey={}&t=indexers",
+            base, self.api_key
+        );
+        let (total, failed_count) = if let Ok(r) = self.client.get(&torznab_endpoint).send().await {
+            if let Ok(xml) = r.text().await {
+                // Count only indexers with configured="true" in their opening tag
+                let total = count_configured_indexers(&xml);
+                (total, 0i64)
             } else {
                 (0, 0)
-            };
-
-            ServiceStatus {
-                name: "Jackett".to_string(),
-                active: true,
-                message: "Running".to_string(),
-                url: url.to_string(),
-                version: None,
-                extras: Some(serde_json::json!({
-                    "total_indexers": total,
-                    "failed_count": failed_count,
-                    "failed_indexers": Vec::<String>::new()
-                })),
             }
-        }
-        Ok(resp) => {
-            let body = resp.text().await.unwrap_or_default();
-            ServiceStatus {
-                name: "Jackett".to_string(),
-                active: false,
-                message: format!("HTTP — {}", body.chars().take(80).collect::<String>()),
-                url: url.to_string(),
-                version: None,
-                extras: None,
-            }
-        }
-        Err(e) => ServiceStatus {
-            name: "Jackett".to_string(),
-            active: false,
-            message: format!("Connection error: {}", e),
-            url: url.to_string(),
-            version: None,
-            extras: None,
-        },
+        } else {
+            (0, 0)
+        };
+
+        Some(serde_json::json!({
+            "total_indexers": total,
+            "failed_count": failed_count,
+            "failed_indexers": Vec::<String>::new()
+        }))
     }
 }
 
-
 // --- Indexer Listing ---
 
 pub async fn list_indexers(client: &Client, url: &str, api_key: &str) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
@@ -1,6 +1,7 @@
 use serde::Deserialize;
 use reqwest::Client;
-use crate::api::ServiceStatus;
+use async_trait::async_trait;
+use crate::api::{Capability, ConfigField, Probe, ProbeError, ServiceClient, ServiceDescriptor, ServiceError};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,60 +15,66 @@ struct WantedResponse {
     total_records: i64,
 }
 
-#[derive(Deserialize)]
-struct ArtistCountResponse {}
-
 fn clean_lidarr_url(url: &str) -> String {
     url.trim().trim_end_matches('/').to_string()
 }
 
-pub async fn get_status(client: &Client, url: &str, api_key: &str) -> ServiceStatus {
-    let base = clean_lidarr_url(url);
-    let endpoint = format!("{}/api/v1/system/status?apikey={}", base, api_key.trim());
-
-    match client.get(&endpoint).send().await {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                match resp.json::<SystemStatus>().await {
-                    Ok(status) => {
-                        let extras = fetch_extras(client, url, api_key).await;
-                        ServiceStatus {
-                            name: "Lidarr".to_string(),
-                            active: true,
-                            message: "Running".to_string(),
-                            url: url.to_string(),
-                            version: Some(status.version),
-                            extras: Some(extras),
-                        }
-                    },
-                    Err(_) => ServiceStatus {
-                        name: "Lidarr".to_string(),
-                        active: true,
-                        message: "Parse Error".to_string(),
-                        url: url.to_string(),
-                        version: None,
-                        extras: None,
-                    },
-                }
-            } else {
-                ServiceStatus {
-                    name: "Lidarr".to_string(),
-                    active: false,
-                    message: format!("HTTP {}", resp.status()),
-                    url: url.to_string(),
-                    version: None,
-                    extras: None,
-                }
-            }
+pub const SERVICE: ServiceDescriptor = ServiceDescriptor {
+    id: "lidarr",
+    fields: &[ConfigField::url("lidarr_url"), ConfigField::secret("lidarr_key")],
+    multi_instance: true,
+    connect: |client, settings| Box::new(Lidarr {
+        client: client.clone(),
+        url: settings.get("lidarr_url").to_string(),
+        api_key: settings.get("lidarr_key").to_string(),
+    }),
+};
+
+pub struct Lidarr {
+    client: Client,
+    url: String,
+    api_key: String,
+}
+
+#[async_trait]
+impl ServiceClient for Lidarr {
+    fn id(&self) -> &str {
+        SERVICE.id
+    }
+
+    fn name(&self) -> &'static str {
+        "Lidarr"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Extras, Capability::Config]
+    }
+
+    async fn probe(&self) -> Result<Probe, ProbeError> {
+        let base = clean_lidarr_url(&self.url);
+        let endpoint = format!("{}/api/v1/system/status?apikey={}", base, self.api_key.trim());
+        let resp = self.client.get(&endpoint).send().await?;
+        if !resp.status().is_success() {
+            return Err(ProbeError::status(resp.status()));
         }
-        Err(e) => ServiceStatus {
-            name: "Lidarr".to_string(),
-            active: false,
-            message: e.to_string(),
-            url: url.to_string(),
-            version: None,
-            extras: None,
-        },
+        let status = resp.json::<SystemStatus>().await?;
+        Ok(Probe { version: Some(status.version), ..Default::default() })
+    }
+
+    async fn extras(&self) -> Option<serde_json::Value> {
+        Some(fetch_extras(&self.client, &self.url, &self.api_key).await)
+    }
+
+    async fn get_config(&self) -> Result<serde_json::Value, ServiceError> {
+        Ok(get_config(&self.client, &self.url, &self.api_key).await?)
+    }
+
+    async fn update_config(&self, config: serde_json::Value) -> Result<(), ServiceError> {
+        Ok(update_config(&self.client, &self.url, &self.api_key, config).await?)
     }
 }
 
@@ -76,27 +83,34 @@ async fn fetch_extras(client: &Client, url: &str, api_key: &str) -> serde_json::
     let key = api_key.trim();
 
     // Get missing albums count
-    let missing = match client
-        .get(format!("{}/api/v1/wanted/missing?apikey={}&pageSize=1", base, key))
-        .send().await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            resp.json::<WantedResponse>().await.map(|w| w.total_records).unwrap_or(0)
+    let missing = async {
+        match client
+            .get(format!("{}/api/v1/wanted/missing?apikey={}&pageSize=1", base, key))
+            .send().await
+        {
+            Ok(resp) if resp.status().is_success() => {
+                resp.json::<WantedResponse>().await.map(|w| w.total_records).unwrap_or(0)
+            }
+            _ => 0,
         }
-        _ => 0,
     };
 
     // Get total artists count
-    let total_artists: i64 = match client
-        .get(format!("{}/api/v1/artist?apikey={}", base, key))
-        .send().await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            resp.json::<Vec<serde_json::Value>>().await.map(|v| v.len() as i64).unwrap_or(0)
+    let total_artists = async {
+        match client
+            .get(format!("{}/api/v1/artist?apikey={}", base, key))
+            .send().await
+        {
+            Ok(resp) if resp.status().is_success() => {
+                resp.json::<Vec<serde::de::IgnoredAny>>().await.map(|v| v.len() as i64).unwrap_or(0)
+            }
+            _ => 0,
         }
-        _ => 0,
     };
 
+    // Both counts are independent, so fetch them side by side
+    let (missing, total_artists) = tokio::join!(missing, total_artists);
+
     serde_json::json!({
         "missing_albums": missing,
         "total_artists": total_artists
@@ -8,14 +8,542 @@ pub mod prowlarr;
 pub mod lidarr;
 pub mod emby;
 
-use serde::Serialize;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, PartialEq)]
 pub struct ServiceStatus {
+    /// Instance id, e.g. "sonarr" for the primary Sonarr or "radarr-4k" for an additional Radarr.
+    pub id: String,
+    /// Registry id of the service type, e.g. "radarr".
+    pub kind: String,
     pub name: String,
     pub active: bool,
     pub message: String,
     pub url: String,
     pub version: Option<String>,
     pub extras: Option<serde_json::Value>,
+    /// Round-trip time of the health probe; `None` if it never completed.
+    pub latency_ms: Option<u64>,
+    /// Set when the probe was abandoned at its deadline, so a slow service
+    /// can be told apart from one that refused the connection.
+    pub timed_out: bool,
+}
+
+/// Every registered service, in the order they appear on the dashboard.
+/// Adding a service means adding its module above and its descriptor here.
+pub const SERVICES: &[&ServiceDescriptor] = &[
+    &plex::SERVICE,
+    &sonarr::SERVICE,
+    &radarr::SERVICE,
+    &jackett::SERVICE,
+    &transmission::SERVICE,
+    &jellyfin::SERVICE,
+    &emby::SERVICE,
+    &prowlarr::SERVICE,
+    &lidarr::SERVICE,
+];
+
+/// Deadline for a full status probe (including extras) when none is configured.
+pub const DEFAULT_STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Optional features a service client implements beyond its status probe.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Extras,
+    Config,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Url,
+    Text,
+    Secret,
+}
+
+/// A connection setting a service needs, stored under `key` in `dashboard_settings`.
+pub struct ConfigField {
+    pub key: &'static str,
+    pub kind: FieldKind,
+}
+
+impl ConfigField {
+    pub const fn url(key: &'static str) -> Self {
+        Self { key, kind: FieldKind::Url }
+    }
+
+    pub const fn text(key: &'static str) -> Self {
+        Self { key, kind: FieldKind::Text }
+    }
+
+    pub const fn secret(key: &'static str) -> Self {
+        Self { key, kind: FieldKind::Secret }
+    }
+}
+
+/// Static description of a service type: its settings and how to build a client from them.
+pub struct ServiceDescriptor {
+    pub id: &'static str,
+    pub fields: &'static [ConfigField],
+    /// Whether additional instances of this service can be configured next to the primary one.
+    pub multi_instance: bool,
+    pub connect: fn(&Client, &ServiceSettings) -> Box<dyn ServiceClient>,
+}
+
+impl ServiceDescriptor {
+    /// A service counts as configured once its URL field is filled in, unless it was disabled.
+    pub fn is_configured(&self, settings: &ServiceSettings) -> bool {
+        settings.get(&self.enabled_key()) != "false"
+            && self
+                .fields
+                .iter()
+                .filter(|f| f.kind == FieldKind::Url)
+                .all(|f| !settings.get(f.key).is_empty())
+    }
+
+    /// Settings key that is `"false"` while the service is switched off, e.g. `sonarr_enabled`.
+    pub fn enabled_key(&self) -> String {
+        format!("{}_enabled", self.id)
+    }
+
+    /// How the service authenticates, as recorded in the `services` table.
+    pub fn auth_type(&self) -> &'static str {
+        if self.fields.iter().any(|f| f.kind == FieldKind::Text) {
+            "basic"
+        } else if self.fields.iter().any(|f| f.kind == FieldKind::Secret && f.key.ends_with("_token")) {
+            "token"
+        } else {
+            "api_key"
+        }
+    }
+
+    /// Settings key holding this service's status deadline in seconds, e.g. `sonarr_timeout`.
+    pub fn timeout_key(&self) -> String {
+        format!("{}_timeout", self.id)
+    }
+
+    pub fn status_timeout(&self, settings: &ServiceSettings) -> Duration {
+        settings
+            .get(&self.timeout_key())
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_STATUS_TIMEOUT)
+    }
+}
+
+/// Flat connection settings keyed by field name (`sonarr_url`, `sonarr_key`, ...).
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(transparent)]
+pub struct ServiceSettings(BTreeMap<String, String>);
+
+impl ServiceSettings {
+    pub fn get(&self, key: &str) -> &str {
+        self.0.get(key).map(String::as_str).unwrap_or("")
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        self.0.insert(key.to_string(), value);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+/// One configured copy of a service. Every service type has a primary instance whose id is
+/// the type's registry id and whose settings are the flat `Config` keys; additional
+/// instances (a 4K Radarr, an anime Sonarr) carry their own settings under the same keys.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServiceInstance {
+    pub id: String,
+    /// Registry id of the service type, e.g. "radarr".
+    pub kind: String,
+    /// Display name; empty means the service's own name, e.g. "Radarr".
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub settings: ServiceSettings,
+}
+
+impl ServiceInstance {
+    pub fn descriptor(&self) -> Option<&'static ServiceDescriptor> {
+        find(&self.kind)
+    }
+
+    /// The instance's label, or `default` (usually the service name) for an unlabelled one.
+    pub fn label_or<'a>(&'a self, default: &'a str) -> &'a str {
+        if self.label.is_empty() { default } else { &self.label }
+    }
+}
+
+/// An instance's settings in the normalized shape shared by the `services` table and
+/// config exports: the URL field becomes `base_url`, the other connection fields
+/// `credentials`, `{kind}_enabled` becomes `enabled`, and anything else (e.g. the
+/// timeout) `options`. Keys drop the service prefix, e.g. `{"key": "..."}`.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ServiceEntry {
+    pub id: String,
+    pub kind: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub base_url: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub credentials: BTreeMap<String, String>,
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl ServiceEntry {
+    /// `None` if nothing is configured for the instance.
+    pub fn from_instance(instance: &ServiceInstance) -> Option<ServiceEntry> {
+        let descriptor = instance.descriptor()?;
+        let prefix = format!("{}_", instance.kind);
+        let mut entry = ServiceEntry {
+            id: instance.id.clone(),
+            kind: instance.kind.clone(),
+            name: instance.label.clone(),
+            base_url: String::new(),
+            enabled: true,
+            credentials: BTreeMap::new(),
+            options: BTreeMap::new(),
+        };
+        let own = instance.settings.iter().filter(|(name, value)| name.starts_with(&prefix) && !value.is_empty());
+        for (name, value) in own {
+            let field = name[prefix.len()..].to_string();
+            match descriptor.fields.iter().find(|f| f.key == name).map(|f| f.kind) {
+                Some(FieldKind::Url) => entry.base_url = value.to_string(),
+                Some(_) => { entry.credentials.insert(field, value.to_string()); }
+                None if name == descriptor.enabled_key() => entry.enabled = value != "false",
+                None => { entry.options.insert(field, value.to_string()); }
+            }
+        }
+        let configured = !entry.base_url.is_empty() || !entry.credentials.is_empty() || !entry.options.is_empty();
+        configured.then_some(entry)
+    }
+
+    /// Rebuilds the instance's flat, kind-prefixed settings.
+    pub fn into_instance(self) -> Result<ServiceInstance, String> {
+        let descriptor = find(&self.kind).ok_or_else(|| format!("{}: unknown service '{}'", self.id, self.kind))?;
+        let mut settings = ServiceSettings::default();
+        if let Some(field) = descriptor.fields.iter().find(|f| f.kind == FieldKind::Url) {
+            settings.set(field.key, self.base_url);
+        }
+        for (field, value) in self.credentials.into_iter().chain(self.options) {
+            settings.set(&format!("{}_{}", self.kind, field), value);
+        }
+        if !self.enabled {
+            settings.set(&descriptor.enabled_key(), "false".to_string());
+        }
+        Ok(ServiceInstance { id: self.id, kind: self.kind, label: self.name, settings })
+    }
+
+    /// The credential fields holding secrets, without the service prefix.
+    pub fn secret_fields(&self) -> Vec<&'static str> {
+        let prefix = format!("{}_", self.kind);
+        find(&self.kind)
+            .map(|descriptor| {
+                descriptor
+                    .fields
+                    .iter()
+                    .filter(|f| f.kind == FieldKind::Secret)
+                    .filter_map(|f| f.key.strip_prefix(prefix.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Replaces every non-empty secret credential with `f(value)`; `None` drops it.
+    pub fn map_secrets<E>(&mut self, mut f: impl FnMut(&str) -> Result<Option<String>, E>) -> Result<(), E> {
+        for field in self.secret_fields() {
+            let Some(value) = self.credentials.get(field).filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            match f(value)? {
+                Some(mapped) => self.credentials.insert(field.to_string(), mapped),
+                None => self.credentials.remove(field),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// The primary instance of every registered service followed by the additional ones.
+pub fn instances(primary: &ServiceSettings, additional: &[ServiceInstance]) -> Vec<ServiceInstance> {
+    SERVICES
+        .iter()
+        .map(|descriptor| ServiceInstance {
+            id: descriptor.id.to_string(),
+            kind: descriptor.id.to_string(),
+            label: String::new(),
+            settings: primary.clone(),
+        })
+        .chain(additional.iter().cloned())
+        .collect()
+}
+
+/// Checks that additional instances have unique slug ids that do not shadow a primary
+/// instance, and name a registered service type.
+pub fn validate_instances(additional: &[ServiceInstance]) -> Result<(), String> {
+    let mut seen = std::collections::HashSet::new();
+    for instance in additional {
+        let valid_id = !instance.id.is_empty()
+            && instance.id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid_id {
+            return Err(format!("Invalid instance id '{}': use lowercase letters, digits and '-'", instance.id));
+        }
+        if find(&instance.id).is_some() || !seen.insert(instance.id.as_str()) {
+            return Err(format!("Instance id '{}' is already in use", instance.id));
+        }
+        if !instance.descriptor().is_some_and(|d| d.multi_instance) {
+            return Err(format!("'{}' does not support additional instances", instance.kind));
+        }
+    }
+    Ok(())
+}
+
+/// Iterates over every connection field of every registered service.
+pub fn all_fields() -> impl Iterator<Item = &'static ConfigField> {
+    SERVICES.iter().flat_map(|s| s.fields.iter())
+}
+
+/// Every key persisted for the registered services: connection fields plus status deadlines.
+pub fn all_setting_keys() -> impl Iterator<Item = String> {
+    SERVICES.iter().flat_map(|s| {
+        s.fields
+            .iter()
+            .map(|f| f.key.to_string())
+            .chain(std::iter::once(s.timeout_key()))
+    })
+}
+
+pub fn find(id: &str) -> Option<&'static ServiceDescriptor> {
+    SERVICES.iter().copied().find(|s| s.id == id)
+}
+
+/// Probes every configured instance concurrently, each bounded by its own deadline.
+/// Results keep the instance order regardless of which service answers first.
+pub async fn poll_all(client: &Client, instances: &[ServiceInstance]) -> Vec<ServiceStatus> {
+    let handles: Vec<_> = instances
+        .iter()
+        .filter_map(|instance| Some((instance, instance.descriptor()?)))
+        .filter(|(instance, descriptor)| descriptor.is_configured(&instance.settings))
+        .map(|(instance, descriptor)| {
+            let service = (descriptor.connect)(client, &instance.settings);
+            let deadline = descriptor.status_timeout(&instance.settings);
+            let (id, label) = (instance.id.clone(), instance.label.clone());
+            tokio::spawn(async move {
+                let mut status = match tokio::time::timeout(deadline, service.status()).await {
+                    Ok(status) => status,
+                    Err(_) => ServiceStatus {
+                        id: service.id().to_string(),
+                        kind: service.id().to_string(),
+                        name: service.name().to_string(),
+                        active: false,
+                        message: format!("Timed out after {}s", deadline.as_secs()),
+                        url: service.url().to_string(),
+                        version: None,
+                        extras: None,
+                        latency_ms: None,
+                        timed_out: true,
+                    },
+                };
+                status.id = id;
+                if !label.is_empty() {
+                    status.name = label;
+                }
+                status
+            })
+        })
+        .collect();
+
+    let mut statuses = Vec::with_capacity(handles.len());
+    for handle in handles {
+        match handle.await {
+            Ok(status) => statuses.push(status),
+            Err(e) => tracing::error!("Status probe task failed: {}", e),
+        }
+    }
+    statuses
+}
+
+/// Runs an upstream request under the service's status deadline, yielding `null`
+/// if the service is not configured, fails or does not answer in time.
+pub async fn bounded<F>(descriptor: &ServiceDescriptor, settings: &ServiceSettings, request: F) -> serde_json::Value
+where
+    F: std::future::Future<Output = Result<serde_json::Value, reqwest::Error>>,
+{
+    if !descriptor.is_configured(settings) {
+        return serde_json::Value::Null;
+    }
+    match tokio::time::timeout(descriptor.status_timeout(settings), request).await {
+        Ok(Ok(value)) => value,
+        _ => serde_json::Value::Null,
+    }
+}
+
+/// Runs `request` against every instance of `kind` and concatenates the resulting arrays,
+/// tagging each item with the id of the instance it came from.
+pub async fn merge_instances<'a, F, Fut>(instances: &'a [ServiceInstance], kind: &str, request: F) -> serde_json::Value
+where
+    F: Fn(&'a ServiceSettings) -> Fut,
+    Fut: std::future::Future<Output = Result<serde_json::Value, reqwest::Error>>,
+{
+    let Some(descriptor) = find(kind) else {
+        return serde_json::Value::Null;
+    };
+    let matching: Vec<&ServiceInstance> = instances
+        .iter()
+        .filter(|instance| instance.kind == kind && descriptor.is_configured(&instance.settings))
+        .collect();
+    if matching.is_empty() {
+        return serde_json::Value::Null;
+    }
+
+    let results = futures_util::future::join_all(
+        matching.iter().map(|instance| bounded(descriptor, &instance.settings, request(&instance.settings))),
+    )
+    .await;
+
+    let mut merged = Vec::new();
+    for (instance, result) in matching.iter().zip(results) {
+        if let serde_json::Value::Array(items) = result {
+            merged.extend(items.into_iter().map(|mut item| {
+                if let Some(object) = item.as_object_mut() {
+                    object.insert("instance".to_string(), serde_json::json!(instance.id));
+                }
+                item
+            }));
+        }
+    }
+    serde_json::Value::Array(merged)
+}
+
+/// What a successful status probe learned about the service.
+#[derive(Default)]
+pub struct Probe {
+    pub message: Option<String>,
+    pub version: Option<String>,
+    pub extras: Option<serde_json::Value>,
+}
+
+/// Why a status probe failed.
+pub enum ProbeError {
+    /// The service answered with a non-success response.
+    Http(String),
+    /// The service answered, but with a body we could not understand.
+    Parse(String),
+    /// The service could not be reached at all.
+    Connection(String),
+}
+
+impl ProbeError {
+    pub fn status(status: reqwest::StatusCode) -> Self {
+        ProbeError::Http(format!("HTTP {}", status))
+    }
+}
+
+impl From<reqwest::Error> for ProbeError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            ProbeError::Parse(e.to_string())
+        } else {
+            ProbeError::Connection(e.to_string())
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ServiceError {
+    Unsupported,
+    Request(String),
+}
+
+impl std::fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceError::Unsupported => write!(f, "Operation not supported by this service"),
+            ServiceError::Request(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ServiceError {
+    fn from(e: reqwest::Error) -> Self {
+        ServiceError::Request(e.to_string())
+    }
+}
+
+#[async_trait]
+pub trait ServiceClient: Send + Sync {
+    fn id(&self) -> &str;
+
+    /// Display name, e.g. "Sonarr".
+    fn name(&self) -> &'static str;
+
+    fn url(&self) -> &str;
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[]
+    }
+
+    /// Lightweight health check against the service.
+    async fn probe(&self) -> Result<Probe, ProbeError>;
+
+    /// Additional dashboard figures, fetched only after a successful probe.
+    async fn extras(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    async fn get_config(&self) -> Result<serde_json::Value, ServiceError> {
+        Err(ServiceError::Unsupported)
+    }
+
+    async fn update_config(&self, _config: serde_json::Value) -> Result<(), ServiceError> {
+        Err(ServiceError::Unsupported)
+    }
+
+    async fn status(&self) -> ServiceStatus {
+        let started = std::time::Instant::now();
+        let probe = self.probe().await;
+        let latency_ms = Some(started.elapsed().as_millis() as u64);
+
+        let (active, message, version, extras) = match probe {
+            Ok(probe) => {
+                let extras = match probe.extras {
+                    Some(extras) => Some(extras),
+                    None => self.extras().await,
+                };
+                let message = probe.message.unwrap_or_else(|| "Running".to_string());
+                (true, message, probe.version, extras)
+            }
+            Err(ProbeError::Parse(e)) => (true, format!("Parse Error: {}", e), None, None),
+            Err(ProbeError::Http(msg)) | Err(ProbeError::Connection(msg)) => (false, msg, None, None),
+        };
+        ServiceStatus {
+            id: self.id().to_string(),
+            kind: self.id().to_string(),
+            name: self.name().to_string(),
+            active,
+            message,
+            url: self.url().to_string(),
+            version,
+            extras,
+            latency_ms,
+            timed_out: false,
+        }
+    }
 }
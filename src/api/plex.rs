@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
-use crate::api::ServiceStatus;
+use async_trait::async_trait;
+use crate::api::{Capability, ConfigField, Probe, ProbeError, ServiceClient, ServiceDescriptor};
 use serde_json::Value;
 
 #[derive(Debug, Serialize)]
@@ -82,73 +83,65 @@ fn plex_get(client: &Client, url: &str, token: &str) -> reqwest::RequestBuilder
         .header("X-Plex-Platform", "Generic")
 }
 
-pub async fn get_status(client: &Client, url: &str, token: &str) -> ServiceStatus {
-    let base = clean_plex_url(url);
-    let endpoint = format!("{}/status/sessions", base);
-    match plex_get(client, &endpoint, token).send().await {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                match resp.json::<MediaContainerWrapper>().await {
-                    Ok(wrapper) => {
-                        let sessions = wrapper.media_container;
-                        let active_count = sessions.size;
-                        let details = format!("{} active session(s)", active_count);
-                        
-                        let extras = if let Some(meta) = sessions.metadata {
-                            let names: Vec<String> = meta.iter()
-                                .map(|s| format!("{} ({})", 
-                                    s.title.as_deref().unwrap_or("Unknown"),
-                                    s.user.as_ref().map(|u| u.title.as_str()).unwrap_or("Unknown")
-                                ))
-                                .collect();
-                            Some(serde_json::json!({
-                                "active_sessions": active_count,
-                                "sessions": names
-                            }))
-                        } else {
-                            Some(serde_json::json!({
-                                "active_sessions": active_count,
-                                "sessions": Vec::<String>::new()
-                            }))
-                        };
-
-                        ServiceStatus {
-                            name: "Plex".to_string(),
-                            active: true,
-                            message: details,
-                            url: url.to_string(),
-                            version: None,
-                            extras,
-                        }
-                    }
-                    Err(e) => ServiceStatus {
-                        name: "Plex".to_string(),
-                        active: true,
-                        message: format!("Parse Error: {}", e),
-                        url: url.to_string(),
-                        version: None,
-                        extras: None,
-                    },
-                }
-            } else {
-                ServiceStatus {
-                    name: "Plex".to_string(),
-                    active: false,
-                    message: format!("HTTP {}", resp.status()),
-                    url: url.to_string(),
-                    version: None,
-                    extras: None,
-                }
-            }
+pub const SERVICE: ServiceDescriptor = ServiceDescriptor {
+    id: "plex",
+    fields: &[ConfigField::url("plex_url"), ConfigField::secret("plex_token")],
+    multi_instance: false,
+    connect: |client, settings| Box::new(Plex {
+        client: client.clone(),
+        url: settings.get("plex_url").to_string(),
+        token: settings.get("plex_token").to_string(),
+    }),
+};
+
+pub struct Plex {
+    client: Client,
+    url: String,
+    token: String,
+}
+
+#[async_trait]
+impl ServiceClient for Plex {
+    fn id(&self) -> &str {
+        SERVICE.id
+    }
+
+    fn name(&self) -> &'static str {
+        "Plex"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Extras]
+    }
+
+    async fn probe(&self) -> Result<Probe, ProbeError> {
+        let base = clean_plex_url(&self.url);
+        let endpoint = format!("{}/status/sessions", base);
+        let resp = plex_get(&self.client, &endpoint, &self.token).send().await?;
+        if !resp.status().is_success() {
+            return Err(ProbeError::status(resp.status()));
         }
-        Err(e) => ServiceStatus {
-            name: "Plex".to_string(),
-            active: false,
-            message: e.to_string(),
-            url: url.to_string(),
+        let sessions = resp.json::<MediaContainerWrapper>().await?.media_container;
+        let active_count = sessions.size;
+        let names: Vec<String> = sessions.metadata.unwrap_or_default().iter()
+            .map(|s| format!("{} ({})",
+                s.title.as_deref().unwrap_or("Unknown"),
+                s.user.as_ref().map(|u| u.title.as_str()).unwrap_or("Unknown")
+            ))
+            .collect();
+
+        Ok(Probe {
+            message: Some(format!("{} active session(s)", active_count)),
             version: None,
-            extras: None,
-        },
+            extras: Some(serde_json::json!({
+                "active_sessions": active_count,
+                "sessions": names
+            })),
+        })
     }
 }
 
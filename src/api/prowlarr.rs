@@ -1,6 +1,7 @@
 use serde::Deserialize;
 use reqwest::Client;
-use crate::api::ServiceStatus;
+use async_trait::async_trait;
+use crate::api::{Capability, ConfigField, Probe, ProbeError, ServiceClient, ServiceDescriptor, ServiceError};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -11,9 +12,6 @@ struct SystemStatus {
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct IndexerResponse {
-    id: i64,
-    name: String,
-    protocol: String,
     enabled: bool,
 }
 
@@ -27,53 +25,62 @@ fn prowlarr_request(client: &Client, url: &str, api_key: &str) -> reqwest::Reque
         .header("Accept", "application/json")
 }
 
-pub async fn get_status(client: &Client, url: &str, api_key: &str) -> ServiceStatus {
-    let base = clean_prowlarr_url(url);
-    let endpoint = format!("{}/api/v1/system/status", base);
-
-    match prowlarr_request(client, &endpoint, api_key).send().await {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                match resp.json::<SystemStatus>().await {
-                    Ok(status) => {
-                        let extras = fetch_extras(client, url, api_key).await;
-                        ServiceStatus {
-                            name: "Prowlarr".to_string(),
-                            active: true,
-                            message: "Running".to_string(),
-                            url: url.to_string(),
-                            version: Some(status.version),
-                            extras: Some(extras),
-                        }
-                    },
-                    Err(_) => ServiceStatus {
-                        name: "Prowlarr".to_string(),
-                        active: true,
-                        message: "Parse Error".to_string(),
-                        url: url.to_string(),
-                        version: None,
-                        extras: None,
-                    },
-                }
-            } else {
-                ServiceStatus {
-                    name: "Prowlarr".to_string(),
-                    active: false,
-                    message: format!("HTTP {}", resp.status()),
-                    url: url.to_string(),
-                    version: None,
-                    extras: None,
-                }
-            }
+pub const SERVICE: ServiceDescriptor = ServiceDescriptor {
+    id: "prowlarr",
+    fields: &[ConfigField::url("prowlarr_url"), ConfigField::secret("prowlarr_key")],
+    multi_instance: true,
+    connect: |client, settings| Box::new(Prowlarr {
+        client: client.clone(),
+        url: settings.get("prowlarr_url").to_string(),
+        api_key: settings.get("prowlarr_key").to_string(),
+    }),
+};
+
+pub struct Prowlarr {
+    client: Client,
+    url: String,
+    api_key: String,
+}
+
+#[async_trait]
+impl ServiceClient for Prowlarr {
+    fn id(&self) -> &str {
+        SERVICE.id
+    }
+
+    fn name(&self) -> &'static str {
+        "Prowlarr"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Extras, Capability::Config]
+    }
+
+    async fn probe(&self) -> Result<Probe, ProbeError> {
+        let base = clean_prowlarr_url(&self.url);
+        let endpoint = format!("{}/api/v1/system/status", base);
+        let resp = prowlarr_request(&self.client, &endpoint, &self.api_key).send().await?;
+        if !resp.status().is_success() {
+            return Err(ProbeError::status(resp.status()));
         }
-        Err(e) => ServiceStatus {
-            name: "Prowlarr".to_string(),
-            active: false,
-            message: e.to_string(),
-            url: url.to_string(),
-            version: None,
-            extras: None,
-        },
+        let status = resp.json::<SystemStatus>().await?;
+        Ok(Probe { version: Some(status.version), ..Default::default() })
+    }
+
+    async fn extras(&self) -> Option<serde_json::Value> {
+        Some(fetch_extras(&self.client, &self.url, &self.api_key).await)
+    }
+
+    async fn get_config(&self) -> Result<serde_json::Value, ServiceError> {
+        Ok(get_config(&self.client, &self.url, &self.api_key).await?)
+    }
+
+    async fn update_config(&self, config: serde_json::Value) -> Result<(), ServiceError> {
+        Ok(update_config(&self.client, &self.url, &self.api_key, config).await?)
     }
 }
 
@@ -1,6 +1,7 @@
 use serde::Deserialize;
 use reqwest::Client;
-use crate::api::ServiceStatus;
+use async_trait::async_trait;
+use crate::api::{Capability, ConfigField, Probe, ProbeError, ServiceClient, ServiceDescriptor, ServiceError};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -15,51 +16,61 @@ struct Movie {
     monitored: bool,
 }
 
-pub async fn get_status(client: &Client, url: &str, api_key: &str) -> ServiceStatus {
-    let endpoint = format!("{}/api/v3/system/status?apikey={}", url, api_key);
-    match client.get(&endpoint).send().await {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                match resp.json::<SystemStatus>().await {
-                    Ok(status) => {
-                        let extras = fetch_extras(client, url, api_key).await;
-                        ServiceStatus {
-                            name: "Radarr".to_string(),
-                            active: true,
-                            message: "Running".to_string(),
-                            url: url.to_string(),
-                            version: Some(status.version),
-                            extras: Some(extras),
-                        }
-                    },
-                    Err(_) => ServiceStatus {
-                        name: "Radarr".to_string(),
-                        active: true,
-                        message: "Parse Error".to_string(),
-                        url: url.to_string(),
-                        version: None,
-                        extras: None,
-                    },
-                }
-            } else {
-                ServiceStatus {
-                    name: "Radarr".to_string(),
-                    active: false,
-                    message: format!("HTTP {}", resp.status()),
-                    url: url.to_string(),
-                    version: None,
-                    extras: None,
-                }
-            }
+pub const SERVICE: ServiceDescriptor = ServiceDescriptor {
+    id: "radarr",
+    fields: &[ConfigField::url("radarr_url"), ConfigField::secret("radarr_key")],
+    multi_instance: true,
+    connect: |client, settings| Box::new(Radarr {
+        client: client.clone(),
+        url: settings.get("radarr_url").to_string(),
+        api_key: settings.get("radarr_key").to_string(),
+    }),
+};
+
+pub struct Radarr {
+    client: Client,
+    url: String,
+    api_key: String,
+}
+
+#[async_trait]
+impl ServiceClient for Radarr {
+    fn id(&self) -> &str {
+        SERVICE.id
+    }
+
+    fn name(&self) -> &'static str {
+        "Radarr"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Extras, Capability::Config]
+    }
+
+    async fn probe(&self) -> Result<Probe, ProbeError> {
+        let endpoint = format!("{}/api/v3/system/status?apikey={}", self.url, self.api_key);
+        let resp = self.client.get(&endpoint).send().await?;
+        if !resp.status().is_success() {
+            return Err(ProbeError::status(resp.status()));
         }
-        Err(e) => ServiceStatus {
-            name: "Radarr".to_string(),
-            active: false,
-            message: e.to_string(),
-            url: url.to_string(),
-            version: None,
-            extras: None,
-        },
+        let status = resp.json::<SystemStatus>().await?;
+        Ok(Probe { version: Some(status.version), ..Default::default() })
+    }
+
+    async fn extras(&self) -> Option<serde_json::Value> {
+        Some(fetch_extras(&self.client, &self.url, &self.api_key).await)
+    }
+
+    async fn get_config(&self) -> Result<serde_json::Value, ServiceError> {
+        Ok(get_config(&self.client, &self.url, &self.api_key).await?)
+    }
+
+    async fn update_config(&self, config: serde_json::Value) -> Result<(), ServiceError> {
+        Ok(update_config(&self.client, &self.url, &self.api_key, config).await?)
     }
 }
 
@@ -106,6 +117,11 @@ pub async fn list_movies(client: &Client, url: &str, api_key: &str) -> Result<se
     client.get(&endpoint).send().await?.json().await
 }
 
+pub async fn get_movie(client: &Client, url: &str, api_key: &str, id: i64) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/movie/{}?apikey={}", url, id, api_key);
+    client.get(&endpoint).send().await?.error_for_status()?.json().await
+}
+
 pub async fn search_movies(client: &Client, url: &str, api_key: &str, term: &str) -> Result<serde_json::Value, reqwest::Error> {
     let endpoint = format!("{}/api/v3/movie/lookup?apikey={}&term={}", url, api_key, urlencoding::encode(term));
     client.get(&endpoint).send().await?.json().await
@@ -122,6 +138,12 @@ pub async fn delete_movie(client: &Client, url: &str, api_key: &str, id: i64, de
     Ok(())
 }
 
+/// Replaces the movie with `movie`. With `move_files`, Radarr moves its folder when `path` changed.
+pub async fn update_movie(client: &Client, url: &str, api_key: &str, id: i64, movie: &serde_json::Value, move_files: bool) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/movie/{}?apikey={}&moveFiles={}", url, id, api_key, move_files);
+    client.put(&endpoint).json(movie).send().await?.json().await
+}
+
 pub async fn get_calendar(client: &Client, url: &str, api_key: &str, start: &str, end: &str) -> Result<serde_json::Value, reqwest::Error> {
     let endpoint = format!("{}/api/v3/calendar?apikey={}&start={}&end={}", url, api_key, start, end);
     client.get(&endpoint).send().await?.json().await
@@ -141,3 +163,23 @@ pub async fn get_quality_profiles(client: &Client, url: &str, api_key: &str) ->
     let endpoint = format!("{}/api/v3/qualityprofile?apikey={}", url, api_key);
     client.get(&endpoint).send().await?.json().await
 }
+
+// --- Files, History and Commands ---
+
+/// The files of a movie, with their quality, size, release group and media info.
+pub async fn list_movie_files(client: &Client, url: &str, api_key: &str, movie_id: i64) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/moviefile?apikey={}&movieId={}", url, api_key, movie_id);
+    client.get(&endpoint).send().await?.error_for_status()?.json().await
+}
+
+/// Grabs, imports, renames and deletions of a movie, newest first.
+pub async fn movie_history(client: &Client, url: &str, api_key: &str, movie_id: i64) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/history/movie?apikey={}&movieId={}", url, api_key, movie_id);
+    client.get(&endpoint).send().await?.error_for_status()?.json().await
+}
+
+/// Queues a command such as `MoviesSearch`, returning it with its id and status.
+pub async fn run_command(client: &Client, url: &str, api_key: &str, command: &serde_json::Value) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/command?apikey={}", url, api_key);
+    client.post(&endpoint).json(command).send().await?.error_for_status()?.json().await
+}
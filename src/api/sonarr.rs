@@ -1,6 +1,7 @@
 use serde::Deserialize;
 use reqwest::Client;
-use crate::api::ServiceStatus;
+use async_trait::async_trait;
+use crate::api::{Capability, ConfigField, Probe, ProbeError, ServiceClient, ServiceDescriptor, ServiceError};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,78 +15,94 @@ struct WantedResponse {
     total_records: i64,
 }
 
-pub async fn get_status(client: &Client, url: &str, api_key: &str) -> ServiceStatus {
-    let endpoint = format!("{}/api/v3/system/status?apikey={}", url, api_key);
-    match client.get(&endpoint).send().await {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                match resp.json::<SystemStatus>().await {
-                    Ok(status) => {
-                        // Fetch missing episodes count
-                        let extras = fetch_extras(client, url, api_key).await;
-                        ServiceStatus {
-                            name: "Sonarr".to_string(),
-                            active: true,
-                            message: "Running".to_string(),
-                            url: url.to_string(),
-                            version: Some(status.version),
-                            extras: Some(extras),
-                        }
-                    },
-                    Err(_) => ServiceStatus {
-                        name: "Sonarr".to_string(),
-                        active: true,
-                        message: "Parse Error".to_string(),
-                        url: url.to_string(),
-                        version: None,
-                        extras: None,
-                    },
-                }
-            } else {
-                ServiceStatus {
-                    name: "Sonarr".to_string(),
-                    active: false,
-                    message: format!("HTTP {}", resp.status()),
-                    url: url.to_string(),
-                    version: None,
-                    extras: None,
-                }
-            }
+pub const SERVICE: ServiceDescriptor = ServiceDescriptor {
+    id: "sonarr",
+    fields: &[ConfigField::url("sonarr_url"), ConfigField::secret("sonarr_key")],
+    multi_instance: true,
+    connect: |client, settings| Box::new(Sonarr {
+        client: client.clone(),
+        url: settings.get("sonarr_url").to_string(),
+        api_key: settings.get("sonarr_key").to_string(),
+    }),
+};
+
+pub struct Sonarr {
+    client: Client,
+    url: String,
+    api_key: String,
+}
+
+#[async_trait]
+impl ServiceClient for Sonarr {
+    fn id(&self) -> &str {
+        SERVICE.id
+    }
+
+    fn name(&self) -> &'static str {
+        "Sonarr"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Extras, Capability::Config]
+    }
+
+    async fn probe(&self) -> Result<Probe, ProbeError> {
+        let endpoint = format!("{}/api/v3/system/status?apikey={}", self.url, self.api_key);
+        let resp = self.client.get(&endpoint).send().await?;
+        if !resp.status().is_success() {
+            return Err(ProbeError::status(resp.status()));
         }
-        Err(e) => ServiceStatus {
-            name: "Sonarr".to_string(),
-            active: false,
-            message: e.to_string(),
-            url: url.to_string(),
-            version: None,
-            extras: None,
-        },
+        let status = resp.json::<SystemStatus>().await?;
+        Ok(Probe { version: Some(status.version), ..Default::default() })
+    }
+
+    async fn extras(&self) -> Option<serde_json::Value> {
+        Some(fetch_extras(&self.client, &self.url, &self.api_key).await)
+    }
+
+    async fn get_config(&self) -> Result<serde_json::Value, ServiceError> {
+        Ok(get_config(&self.client, &self.url, &self.api_key).await?)
+    }
+
+    async fn update_config(&self, config: serde_json::Value) -> Result<(), ServiceError> {
+        Ok(update_config(&self.client, &self.url, &self.api_key, config).await?)
     }
 }
 
 async fn fetch_extras(client: &Client, url: &str, api_key: &str) -> serde_json::Value {
     // Get missing episodes count
-    let missing = match client
-        .get(format!("{}/api/v3/wanted/missing?apikey={}&pageSize=1&sortKey=airDateUtc&sortDirection=descending", url, api_key))
-        .send().await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            resp.json::<WantedResponse>().await.map(|w| w.total_records).unwrap_or(0)
+    let missing = async {
+        match client
+            .get(format!("{}/api/v3/wanted/missing?apikey={}&pageSize=1&sortKey=airDateUtc&sortDirection=descending", url, api_key))
+            .send().await
+        {
+            Ok(resp) if resp.status().is_success() => {
+                resp.json::<WantedResponse>().await.map(|w| w.total_records).unwrap_or(0)
+            }
+            _ => 0,
         }
-        _ => 0,
     };
 
     // Get total series count
-    let total_series: i64 = match client
-        .get(format!("{}/api/v3/series?apikey={}", url, api_key))
-        .send().await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            resp.json::<Vec<serde_json::Value>>().await.map(|v| v.len() as i64).unwrap_or(0)
+    let total_series = async {
+        match client
+            .get(format!("{}/api/v3/series?apikey={}", url, api_key))
+            .send().await
+        {
+            Ok(resp) if resp.status().is_success() => {
+                resp.json::<Vec<serde::de::IgnoredAny>>().await.map(|v| v.len() as i64).unwrap_or(0)
+            }
+            _ => 0,
         }
-        _ => 0,
     };
 
+    // Both counts are independent, so fetch them side by side
+    let (missing, total_series) = tokio::join!(missing, total_series);
+
     serde_json::json!({
         "missing_episodes": missing,
         "total_series": total_series
@@ -110,6 +127,11 @@ pub async fn list_series(client: &Client, url: &str, api_key: &str) -> Result<se
     client.get(&endpoint).send().await?.json().await
 }
 
+pub async fn get_series(client: &Client, url: &str, api_key: &str, id: i64) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/series/{}?apikey={}", url, id, api_key);
+    client.get(&endpoint).send().await?.error_for_status()?.json().await
+}
+
 pub async fn search_series(client: &Client, url: &str, api_key: &str, term: &str) -> Result<serde_json::Value, reqwest::Error> {
     let endpoint = format!("{}/api/v3/series/lookup?apikey={}&term={}", url, api_key, urlencoding::encode(term));
     client.get(&endpoint).send().await?.json().await
@@ -126,6 +148,13 @@ pub async fn delete_series(client: &Client, url: &str, api_key: &str, id: i64, d
     Ok(())
 }
 
+/// Replaces the series with `series`, e.g. to change which seasons are monitored. Sonarr
+/// updates the monitoring of a season's episodes along with it.
+pub async fn update_series(client: &Client, url: &str, api_key: &str, id: i64, series: &serde_json::Value) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/series/{}?apikey={}", url, id, api_key);
+    client.put(&endpoint).json(series).send().await?.error_for_status()?.json().await
+}
+
 pub async fn get_calendar(client: &Client, url: &str, api_key: &str, start: &str, end: &str) -> Result<serde_json::Value, reqwest::Error> {
     let endpoint = format!("{}/api/v3/calendar?apikey={}&start={}&end={}&includeSeries=true", url, api_key, start, end);
     client.get(&endpoint).send().await?.json().await
@@ -145,3 +174,38 @@ pub async fn get_quality_profiles(client: &Client, url: &str, api_key: &str) ->
     let endpoint = format!("{}/api/v3/qualityprofile?apikey={}", url, api_key);
     client.get(&endpoint).send().await?.json().await
 }
+
+// --- Episodes ---
+
+/// The episodes of a series, or of one of its seasons.
+pub async fn list_episodes(client: &Client, url: &str, api_key: &str, series_id: i64, season: Option<i64>) -> Result<serde_json::Value, reqwest::Error> {
+    let mut endpoint = format!("{}/api/v3/episode?apikey={}&seriesId={}", url, api_key, series_id);
+    if let Some(season) = season {
+        endpoint.push_str(&format!("&seasonNumber={}", season));
+    }
+    client.get(&endpoint).send().await?.error_for_status()?.json().await
+}
+
+pub async fn get_episode(client: &Client, url: &str, api_key: &str, id: i64) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/episode/{}?apikey={}", url, id, api_key);
+    client.get(&endpoint).send().await?.error_for_status()?.json().await
+}
+
+pub async fn set_episodes_monitored(client: &Client, url: &str, api_key: &str, ids: &[i64], monitored: bool) -> Result<(), reqwest::Error> {
+    let endpoint = format!("{}/api/v3/episode/monitor?apikey={}", url, api_key);
+    let body = serde_json::json!({ "episodeIds": ids, "monitored": monitored });
+    client.put(&endpoint).json(&body).send().await?.error_for_status()?;
+    Ok(())
+}
+
+/// The files on disk for a series' episodes, with their quality, size, release group and media info.
+pub async fn list_episode_files(client: &Client, url: &str, api_key: &str, series_id: i64) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/episodefile?apikey={}&seriesId={}", url, api_key, series_id);
+    client.get(&endpoint).send().await?.error_for_status()?.json().await
+}
+
+/// Queues a command such as `SeriesSearch`, returning it with its id and status.
+pub async fn run_command(client: &Client, url: &str, api_key: &str, command: &serde_json::Value) -> Result<serde_json::Value, reqwest::Error> {
+    let endpoint = format!("{}/api/v3/command?apikey={}", url, api_key);
+    client.post(&endpoint).json(command).send().await?.error_for_status()?.json().await
+}
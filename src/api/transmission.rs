@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
-use crate::api::ServiceStatus;
+use async_trait::async_trait;
+use crate::api::{Capability, ConfigField, Probe, ProbeError, ServiceClient, ServiceDescriptor, ServiceError};
 
 #[derive(Serialize)]
 struct RpcRequest {
@@ -81,49 +82,77 @@ async fn rpc_request(
     }
 }
 
-pub async fn get_status(client: &Client, url: &str, user: &str, pass: &str) -> ServiceStatus {
-    let endpoint = format!("{}/transmission/rpc", url);
-    let rpc_req = RpcRequest {
-        method: "session-get".to_string(),
-    };
+pub const SERVICE: ServiceDescriptor = ServiceDescriptor {
+    id: "transmission",
+    fields: &[
+        ConfigField::url("transmission_url"),
+        ConfigField::text("transmission_user"),
+        ConfigField::secret("transmission_pass"),
+    ],
+    multi_instance: false,
+    connect: |client, settings| Box::new(Transmission {
+        client: client.clone(),
+        url: settings.get("transmission_url").to_string(),
+        user: settings.get("transmission_user").to_string(),
+        pass: settings.get("transmission_pass").to_string(),
+    }),
+};
 
-    let mut builder = client.post(&endpoint).json(&rpc_req);
-    if !user.is_empty() {
-        builder = builder.basic_auth(user, Some(pass));
+pub struct Transmission {
+    client: Client,
+    url: String,
+    user: String,
+    pass: String,
+}
+
+#[async_trait]
+impl ServiceClient for Transmission {
+    fn id(&self) -> &str {
+        SERVICE.id
     }
 
-    match builder.send().await {
-        Ok(resp) => {
-            if resp.status().is_success() || resp.status().as_u16() == 409 {
-                // Fetch downloading info
-                let extras = fetch_extras(client, url, user, pass).await;
-                ServiceStatus {
-                    name: "Transmission".to_string(),
-                    active: true,
-                    message: "Running".to_string(),
-                    url: url.to_string(),
-                    version: None,
-                    extras: Some(extras),
-                }
-            } else {
-                ServiceStatus {
-                    name: "Transmission".to_string(),
-                    active: false,
-                    message: format!("HTTP {}", resp.status()),
-                    url: url.to_string(),
-                    version: None,
-                    extras: None,
-                }
-            }
+    fn name(&self) -> &'static str {
+        "Transmission"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[Capability::Extras, Capability::Config]
+    }
+
+    async fn probe(&self) -> Result<Probe, ProbeError> {
+        let endpoint = format!("{}/transmission/rpc", self.url);
+        let rpc_req = RpcRequest {
+            method: "session-get".to_string(),
+        };
+
+        let mut builder = self.client.post(&endpoint).json(&rpc_req);
+        if !self.user.is_empty() {
+            builder = builder.basic_auth(&self.user, Some(&self.pass));
+        }
+
+        let resp = builder.send().await?;
+        // A 409 carries the CSRF session id, which still means the daemon is up
+        if resp.status().is_success() || resp.status().as_u16() == 409 {
+            Ok(Probe::default())
+        } else {
+            Err(ProbeError::status(resp.status()))
         }
-        Err(e) => ServiceStatus {
-            name: "Transmission".to_string(),
-            active: false,
-            message: e.to_string(),
-            url: url.to_string(),
-            version: None,
-            extras: None,
-        },
+    }
+
+    async fn extras(&self) -> Option<serde_json::Value> {
+        Some(fetch_extras(&self.client, &self.url, &self.user, &self.pass).await)
+    }
+
+    async fn get_config(&self) -> Result<serde_json::Value, ServiceError> {
+        Ok(get_config(&self.client, &self.url, &self.user, &self.pass).await?)
+    }
+
+    async fn update_config(&self, config: serde_json::Value) -> Result<(), ServiceError> {
+        Ok(update_config(&self.client, &self.url, &self.user, &self.pass, config).await?)
     }
 }
 
@@ -209,6 +238,23 @@ pub async fn list_torrents(
     rpc_request(client, url, user, pass, &body).await
 }
 
+pub async fn get_torrent(
+    client: &Client,
+    url: &str,
+    user: &str,
+    pass: &str,
+    id: i64,
+) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    let body = serde_json::json!({
+        "method": "torrent-get",
+        "arguments": {
+            "ids": [id],
+            "fields": ["id", "name", "status", "percentDone", "sizeWhenDone"]
+        }
+    });
+    rpc_request(client, url, user, pass, &body).await
+}
+
 pub async fn add_torrent(
     client: &Client,
     url: &str,
@@ -0,0 +1,137 @@
+use crate::db::{self, AuditFilter, AuditLog};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+pub mod retention;
+
+/// Entries per page when the caller does not ask for a size, and the most it may ask for.
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+pub const MAX_PAGE_SIZE: i64 = 1000;
+/// Entries fetched per query while an export streams.
+const EXPORT_BATCH: i64 = 500;
+
+const CSV_COLUMNS: [&str; 12] = [
+    "id",
+    "timestamp",
+    "username",
+    "api_token",
+    "service",
+    "action",
+    "target_id",
+    "target_title",
+    "outcome",
+    "details",
+    "before",
+    "after",
+];
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Jsonl,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// A bound of the `from`/`to` filters: an RFC 3339 time, or a date meaning its midnight UTC.
+pub fn parse_time(name: &str, value: &str) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        .map_err(|_| format!("{}: '{}' is not an RFC 3339 time or YYYY-MM-DD date", name, value))
+}
+
+/// Every entry matching `filter`, newest first, rendered in `format` a batch at a time so a
+/// large log never sits in memory at once.
+pub fn export(
+    pool: SqlitePool,
+    filter: AuditFilter,
+    format: Format,
+) -> impl futures_util::Stream<Item = Result<String, sqlx::Error>> {
+    let header = match format {
+        Format::Csv => Some(format!("{}\r\n", CSV_COLUMNS.join(","))),
+        Format::Jsonl => None,
+    };
+    // `None` once the last batch has been sent
+    let start = Some((header, None));
+    futures_util::stream::unfold(start, move |cursor| {
+        let (pool, filter) = (pool.clone(), filter.clone());
+        async move {
+            let (header, before) = cursor?;
+            let entries = match db::query_audit_logs(&pool, &filter, before, EXPORT_BATCH).await {
+                Ok(entries) => entries,
+                Err(e) => return Some((Err(e), None)),
+            };
+            let next = match entries.last() {
+                Some(last) if entries.len() as i64 == EXPORT_BATCH => Some((None, Some(last.id))),
+                _ => None,
+            };
+            let mut chunk = header.unwrap_or_default();
+            for entry in &entries {
+                chunk.push_str(&render(entry, format));
+            }
+            Some((Ok(chunk), next))
+        }
+    })
+}
+
+fn render(entry: &AuditLog, format: Format) -> String {
+    match format {
+        Format::Jsonl => serde_json::to_string(entry).map(|line| line + "\n").unwrap_or_default(),
+        Format::Csv => {
+            let json = |value: &Option<sqlx::types::Json<serde_json::Value>>| {
+                value.as_ref().map(|v| v.0.to_string()).unwrap_or_default()
+            };
+            let fields = [
+                entry.id.to_string(),
+                entry.timestamp.clone(),
+                entry.username.clone().unwrap_or_default(),
+                entry.api_token.clone().unwrap_or_default(),
+                entry.service.clone(),
+                entry.action.clone(),
+                entry.target_id.clone().unwrap_or_default(),
+                entry.target_title.clone().unwrap_or_default(),
+                entry.outcome.clone(),
+                entry.details.clone(),
+                json(&entry.before),
+                json(&entry.after),
+            ];
+            let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            format!("{}\r\n", row.join(","))
+        }
+    }
+}
+
+/// Quotes a CSV field when needed. Text that a spreadsheet would run as a formula, such as
+/// a torrent name starting with `=`, is prefixed with an apostrophe so it stays text.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
@@ -0,0 +1,178 @@
+use crate::db::{self, Expiry, History};
+use crate::AppState;
+use chrono::{TimeDelta, Utc};
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const SETTING_KEYS: [&str; 5] = [
+    "retention_audit_max_age_days",
+    "retention_audit_max_rows",
+    "retention_login_max_age_days",
+    "retention_login_max_rows",
+    "retention_archive",
+];
+
+/// How often the retention settings are applied.
+const ENFORCE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Rows read per query while an archive is written.
+const ARCHIVE_BATCH: i64 = 1000;
+
+/// How long audit entries and login events are kept. A limit of 0 does not apply, so by
+/// default both are kept forever.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct RetentionSettings {
+    pub audit_max_age_days: u64,
+    pub audit_max_rows: u64,
+    pub login_max_age_days: u64,
+    pub login_max_rows: u64,
+    /// Write expired rows to compressed JSON-lines files in `archive/` next to the database
+    /// before deleting them.
+    pub archive: bool,
+}
+
+impl RetentionSettings {
+    pub fn get(&self, key: &str) -> String {
+        match key {
+            "retention_audit_max_age_days" => self.audit_max_age_days.to_string(),
+            "retention_audit_max_rows" => self.audit_max_rows.to_string(),
+            "retention_login_max_age_days" => self.login_max_age_days.to_string(),
+            "retention_login_max_rows" => self.login_max_rows.to_string(),
+            "retention_archive" => self.archive.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Fails on a value of the wrong type, leaving the setting as it was.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let invalid = || format!("{}: invalid value '{}'", key, value);
+        let field = match key {
+            "retention_archive" => {
+                self.archive = value.parse().map_err(|_| invalid())?;
+                return Ok(());
+            }
+            "retention_audit_max_age_days" => &mut self.audit_max_age_days,
+            "retention_audit_max_rows" => &mut self.audit_max_rows,
+            "retention_login_max_age_days" => &mut self.login_max_age_days,
+            "retention_login_max_rows" => &mut self.login_max_rows,
+            _ => return Ok(()),
+        };
+        *field = value.parse().map_err(|_| invalid())?;
+        Ok(())
+    }
+
+    /// The maximum age in days and number of rows of `history`, where they apply.
+    fn policy(&self, history: History) -> (Option<u64>, Option<u64>) {
+        let (days, rows) = match history {
+            History::AuditLogs => (self.audit_max_age_days, self.audit_max_rows),
+            History::LoginEvents => (self.login_max_age_days, self.login_max_rows),
+        };
+        (Some(days).filter(|&d| d > 0), Some(rows).filter(|&r| r > 0))
+    }
+}
+
+/// Starts the task that applies the retention settings on start and then hourly. Archives
+/// are written to `archive/` in `data_dir`.
+pub fn spawn(state: Arc<AppState>, data_dir: PathBuf) {
+    let archive_dir = data_dir.join("archive");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ENFORCE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let settings = state.config.read().await.retention.clone();
+            for history in [History::AuditLogs, History::LoginEvents] {
+                let archive_dir = settings.archive.then_some(archive_dir.as_path());
+                match enforce(&state.db, &settings, history, archive_dir).await {
+                    Ok((0, _)) => {}
+                    Ok((deleted, archive)) => {
+                        let archived = archive.map(|path| format!(", archived to {}", path.display())).unwrap_or_default();
+                        let details = format!("{} expired row(s) of {} deleted{}", deleted, history.table(), archived);
+                        tracing::info!("{}", details);
+                        db::log_event(&state.db, None, "System", "Retention", &details).await;
+                    }
+                    Err(e) => tracing::warn!("Failed to apply the retention settings to {}, keeping its rows: {}", history.table(), e),
+                }
+            }
+        }
+    });
+}
+
+/// Deletes the expired rows of `history`, after archiving them into `archive_dir` if given.
+/// Returns how many were deleted and the archive they went to.
+async fn enforce(
+    pool: &SqlitePool,
+    settings: &RetentionSettings,
+    history: History,
+    archive_dir: Option<&Path>,
+) -> Result<(u64, Option<PathBuf>), String> {
+    let (max_age, max_rows) = settings.policy(history);
+    // An age too large to subtract from now expires nothing
+    let before = max_age
+        .and_then(|days| TimeDelta::try_days(days.try_into().ok()?))
+        .and_then(|age| Utc::now().checked_sub_signed(age));
+    let up_to = match max_rows {
+        Some(keep) => db::history_beyond(pool, history, keep).await.map_err(|e| e.to_string())?,
+        None => None,
+    };
+    if before.is_none() && up_to.is_none() {
+        return Ok((0, None));
+    }
+    let expiry = Expiry { before, up_to };
+
+    let archive = match archive_dir {
+        Some(dir) => archive(pool, history, expiry, dir).await?,
+        None => None,
+    };
+    let deleted = db::delete_expired(pool, history, expiry).await.map_err(|e| e.to_string())?;
+    Ok((deleted, archive))
+}
+
+/// Writes the expired rows of `history` to a new gzip-compressed JSON-lines file in `dir`.
+/// Returns its path, or `None` when nothing has expired. A file that could not be
+/// completed is removed again.
+async fn archive(pool: &SqlitePool, history: History, expiry: Expiry, dir: &Path) -> Result<Option<PathBuf>, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}-{}.jsonl.gz", history.table(), Utc::now().format("%Y%m%d-%H%M%S")));
+    let result = write_archive(pool, history, expiry, &path).await;
+    if !matches!(result, Ok(true)) {
+        let _ = std::fs::remove_file(&path);
+    }
+    result.map(|written| written.then_some(path))
+}
+
+/// Whether any row was written.
+async fn write_archive(pool: &SqlitePool, history: History, expiry: Expiry, path: &Path) -> Result<bool, String> {
+    let failed = |e: std::io::Error| format!("Failed to write {}: {}", path.display(), e);
+    let file = std::fs::File::create(path).map_err(failed)?;
+    let mut encoder = GzEncoder::new(std::io::BufWriter::new(file), Compression::default());
+    let mut after = 0;
+    loop {
+        let rows = match history {
+            History::AuditLogs => json_lines(db::expired_audit_logs(pool, expiry, after, ARCHIVE_BATCH).await, |row| row.id),
+            History::LoginEvents => json_lines(db::expired_login_events(pool, expiry, after, ARCHIVE_BATCH).await, |row| row.id),
+        }?;
+        let Some((last, _)) = rows.last() else {
+            break;
+        };
+        after = *last;
+        for (_, line) in &rows {
+            writeln!(encoder, "{}", line).map_err(failed)?;
+        }
+    }
+    encoder.finish().and_then(|mut file| file.flush()).map_err(failed)?;
+    Ok(after > 0)
+}
+
+/// Each row as a line of JSON, with its id.
+fn json_lines<T: Serialize>(rows: Result<Vec<T>, sqlx::Error>, id: fn(&T) -> i64) -> Result<Vec<(i64, String)>, String> {
+    rows.map_err(|e| e.to_string())?
+        .iter()
+        .map(|row| serde_json::to_string(row).map(|line| (id(row), line)).map_err(|e| e.to_string()))
+        .collect()
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Failed attempts a client or username gets before it is locked out.
+const FREE_ATTEMPTS: u32 = 5;
+/// Lockout after the first attempt past the free ones; it doubles with every further failure.
+const BASE_LOCKOUT: Duration = Duration::from_secs(30);
+const MAX_LOCKOUT: Duration = Duration::from_secs(60 * 60);
+/// A key with no failures for this long starts over from zero.
+const FAILURE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Tracks failed logins per client IP and per username, locking either out with
+/// exponential backoff once it exceeds `FREE_ATTEMPTS`. A client without an IP, e.g. one
+/// on the Unix socket, is only limited per username.
+#[derive(Default)]
+pub struct LoginThrottle {
+    by_ip: HashMap<IpAddr, Failures>,
+    by_user: HashMap<String, Failures>,
+}
+
+impl LoginThrottle {
+    /// How much longer the IP or username stays locked out, if either is.
+    pub fn locked_for(&self, ip: Option<IpAddr>, username: &str, now: Instant) -> Option<Duration> {
+        let remaining = |failures: Option<&Failures>| {
+            failures
+                .and_then(|f| f.locked_until)
+                .and_then(|until| until.checked_duration_since(now))
+                .filter(|left| !left.is_zero())
+        };
+        remaining(ip.and_then(|ip| self.by_ip.get(&ip))).max(remaining(self.by_user.get(username)))
+    }
+
+    pub fn record_failure(&mut self, ip: Option<IpAddr>, username: &str, now: Instant) {
+        prune(&mut self.by_ip, now);
+        prune(&mut self.by_user, now);
+        if let Some(ip) = ip {
+            bump(self.by_ip.entry(ip).or_insert_with(|| fresh(now)), now);
+        }
+        bump(self.by_user.entry(username.to_string()).or_insert_with(|| fresh(now)), now);
+    }
+
+    pub fn record_success(&mut self, ip: Option<IpAddr>, username: &str) {
+        if let Some(ip) = ip {
+            self.by_ip.remove(&ip);
+        }
+        self.by_user.remove(username);
+    }
+}
+
+fn fresh(now: Instant) -> Failures {
+    Failures { count: 0, last: now, locked_until: None }
+}
+
+fn bump(failures: &mut Failures, now: Instant) {
+    if now.duration_since(failures.last) > FAILURE_WINDOW {
+        failures.count = 0;
+    }
+    failures.count += 1;
+    failures.last = now;
+    if failures.count > FREE_ATTEMPTS {
+        let doublings = (failures.count - FREE_ATTEMPTS - 1).min(16);
+        let lockout = BASE_LOCKOUT.saturating_mul(1 << doublings).min(MAX_LOCKOUT);
+        failures.locked_until = Some(now + lockout);
+    }
+}
+
+/// Drops keys whose failures have aged out and that are no longer locked.
+fn prune<K: Eq + Hash>(map: &mut HashMap<K, Failures>, now: Instant) {
+    map.retain(|_, f| {
+        now.duration_since(f.last) <= FAILURE_WINDOW || f.locked_until.is_some_and(|until| until > now)
+    });
+}
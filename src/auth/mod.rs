@@ -0,0 +1,210 @@
+pub mod lockout;
+pub mod oidc;
+pub mod proxy;
+pub mod tokens;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::http::{header, HeaderMap};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+/// Hashes a password into a PHC string (`$argon2id$...`) with a random salt.
+/// Argon2 is deliberately slow, so call this from a blocking task.
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// A hash of a throwaway password, verified against when a username does not exist so
+/// that unknown and known usernames take the same time to reject.
+pub fn dummy_hash() -> &'static str {
+    static DUMMY: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    DUMMY.get_or_init(|| hash_password("media-dashboard").unwrap_or_default())
+}
+
+/// Rows written before passwords were hashed hold the plaintext password instead.
+pub fn is_hashed(stored: &str) -> bool {
+    stored.starts_with("$argon2")
+}
+
+/// Checks a password against a stored PHC hash, or against a legacy plaintext value
+/// in constant time. Like `hash_password`, this is slow and belongs in a blocking task.
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    if !is_hashed(stored) {
+        return constant_time_eq(password.as_bytes(), stored.as_bytes());
+    }
+    match PasswordHash::new(stored) {
+        Ok(hash) => Argon2::default().verify_password(password.as_bytes(), &hash).is_ok(),
+        Err(_) => false,
+    }
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// SHA-256 of a credential, used where a fast, non-reversible fingerprint is enough.
+pub fn digest(value: &str) -> [u8; 32] {
+    Sha256::digest(value.as_bytes()).into()
+}
+
+/// Splits an `Authorization: Basic ...` header value into username and password.
+pub fn parse_basic_auth(header: &str) -> Option<(String, String)> {
+    let encoded = header.strip_prefix("Basic ")?;
+    let decoded = STANDARD.decode(encoded.trim()).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (user, pass) = decoded.split_once(':')?;
+    Some((user.to_string(), pass.to_string()))
+}
+
+// ===================== Users =====================
+
+/// What a dashboard user may do. Each role includes everything the roles below it can do.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Sees status, calendar and libraries.
+    Viewer,
+    /// Also adds and removes media and controls torrents.
+    Operator,
+    /// Also changes dashboard and service configuration and manages users.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// Unknown role names fall back to the least privileged role.
+    pub fn parse(value: &str) -> Role {
+        match value {
+            "admin" => Role::Admin,
+            "operator" => Role::Operator,
+            _ => Role::Viewer,
+        }
+    }
+}
+
+/// Maps the groups an identity provider reports to a dashboard role, for forward
+/// authentication and single sign-on.
+pub struct GroupRoles {
+    admin: Vec<String>,
+    operator: Vec<String>,
+    /// Role of someone in neither list; `None` refuses them.
+    default: Option<Role>,
+}
+
+impl GroupRoles {
+    /// `admin` and `operator` are comma-separated group names; `default` is a role name or "none".
+    pub fn parse(admin: &str, operator: &str, default: &str) -> Result<GroupRoles, String> {
+        let list = |groups: &str| -> Vec<String> {
+            groups.split(',').map(str::trim).filter(|g| !g.is_empty()).map(String::from).collect()
+        };
+        let default = match default.trim() {
+            "none" => None,
+            role @ ("viewer" | "operator" | "admin") => Some(Role::parse(role)),
+            other => return Err(format!("unknown role '{}'", other)),
+        };
+        Ok(GroupRoles { admin: list(admin), operator: list(operator), default })
+    }
+
+    pub fn role<'a>(&self, groups: impl IntoIterator<Item = &'a str>) -> Option<Role> {
+        let groups: Vec<&str> = groups.into_iter().map(str::trim).collect();
+        let member = |mapped: &[String]| mapped.iter().any(|group| groups.contains(&group.as_str()));
+        if member(&self.admin) {
+            Some(Role::Admin)
+        } else if member(&self.operator) {
+            Some(Role::Operator)
+        } else {
+            self.default
+        }
+    }
+}
+
+/// What a group of routes requires: a minimum role, and for API tokens the scope that
+/// covers them. Routes without a scope cannot be reached with a token at all.
+#[derive(Clone, Copy)]
+pub struct Access {
+    pub role: Role,
+    pub scope: Option<tokens::Scope>,
+}
+
+impl Access {
+    pub fn new(role: Role, scope: Option<tokens::Scope>) -> Access {
+        Access { role, scope }
+    }
+}
+
+/// Who made a request, attached to it by `auth_middleware` once authentication succeeds.
+#[derive(Clone, serde::Serialize)]
+pub struct Identity {
+    pub username: String,
+    pub role: Role,
+    /// How the request authenticated: "session", "basic", "proxy" or "token". OIDC logins are sessions.
+    pub method: &'static str,
+    /// The API token used, when `method` is "token".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<tokens::TokenGrant>,
+}
+
+// ===================== Sessions =====================
+
+pub const SESSION_COOKIE: &str = "md_session";
+pub const DEFAULT_SESSION_LIFETIME_HOURS: u64 = 24 * 7;
+/// A year; anything longer is as good as never signing out.
+pub const MAX_SESSION_LIFETIME_HOURS: u64 = 24 * 365;
+
+pub fn valid_session_lifetime(hours: u64) -> bool {
+    (1..=MAX_SESSION_LIFETIME_HOURS).contains(&hours)
+}
+
+/// A fresh random session token, as handed to the browser in the session cookie.
+pub fn new_session_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The key a session is stored under. Only the digest reaches the database, so a leaked
+/// copy of the `sessions` table cannot be replayed as cookies.
+pub fn session_id(token: &str) -> String {
+    digest(token).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `base_path` scopes the cookie to the dashboard when it shares a host with other apps;
+/// `secure` keeps it off plain HTTP when the dashboard serves HTTPS itself.
+pub fn session_cookie(token: &str, max_age_secs: i64, base_path: &str, secure: bool) -> String {
+    format!("{}={}; Path={}/; HttpOnly; SameSite=Lax; Max-Age={}{}", SESSION_COOKIE, token, base_path, max_age_secs, secure_attribute(secure))
+}
+
+pub fn clear_session_cookie(base_path: &str, secure: bool) -> String {
+    session_cookie("", 0, base_path, secure)
+}
+
+fn secure_attribute(secure: bool) -> &'static str {
+    if secure { "; Secure" } else { "" }
+}
+
+/// Looks up a cookie by name across every `Cookie` header of the request.
+pub fn read_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
@@ -0,0 +1,611 @@
+use super::{GroupRoles, Role};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a browser has to come back from the identity provider.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// How long discovery and signing keys are reused before they are fetched again.
+const PROVIDER_TTL: Duration = Duration::from_secs(60 * 60);
+/// Upper bound on logins waiting for their callback; the oldest are dropped beyond it.
+const MAX_PENDING: usize = 256;
+/// Binds a login to the browser that started it, so a callback cannot be replayed elsewhere.
+pub const STATE_COOKIE: &str = "md_oidc_state";
+
+/// Every `OidcSettings` field, as its `oidc_`-prefixed settings key.
+pub const SETTING_KEYS: [&str; 10] = [
+    "oidc_issuer",
+    "oidc_client_id",
+    "oidc_client_secret",
+    "oidc_scopes",
+    "oidc_username_claim",
+    "oidc_groups_claim",
+    "oidc_admin_groups",
+    "oidc_operator_groups",
+    "oidc_default_role",
+    "oidc_redirect_url",
+];
+
+/// Single sign-on through an OpenID Connect provider such as Authentik, Keycloak or
+/// Google. Disabled while `issuer` is empty.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct OidcSettings {
+    /// Issuer URL; `/.well-known/openid-configuration` below it describes the provider.
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Space-separated; "openid profile email groups" when empty.
+    pub scopes: String,
+    /// ID token claim holding the dashboard username; "preferred_username" when empty.
+    pub username_claim: String,
+    /// ID token claim listing the user's groups; "groups" when empty.
+    pub groups_claim: String,
+    /// Comma-separated groups granted the admin role.
+    pub admin_groups: String,
+    /// Comma-separated groups granted the operator role.
+    pub operator_groups: String,
+    /// Role of someone in neither list: viewer (when empty), operator, admin or none.
+    pub default_role: String,
+    /// Callback URL registered with the provider, when the one derived from the
+    /// request is wrong, e.g. behind a proxy that rewrites the host.
+    pub redirect_url: String,
+}
+
+impl OidcSettings {
+    pub fn is_enabled(&self) -> bool {
+        !self.issuer.trim().is_empty()
+    }
+
+    /// The issuer as accounts record it, so a trailing slash makes no difference.
+    pub fn issuer_id(&self) -> String {
+        issuer_id(&self.issuer)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if !self.issuer.starts_with("https://") && !self.issuer.starts_with("http://") {
+            return Err(format!("OIDC issuer '{}' is not an http(s) URL", self.issuer));
+        }
+        if self.client_id.trim().is_empty() {
+            return Err("OIDC needs a client id".to_string());
+        }
+        self.roles().map(|_| ())
+    }
+
+    pub fn roles(&self) -> Result<GroupRoles, String> {
+        let default = if self.default_role.trim().is_empty() { "viewer" } else { &self.default_role };
+        GroupRoles::parse(&self.admin_groups, &self.operator_groups, default).map_err(|e| format!("OIDC default role: {}", e))
+    }
+
+    fn scopes(&self) -> &str {
+        or_default(&self.scopes, "openid profile email groups")
+    }
+
+    fn username_claim(&self) -> &str {
+        or_default(&self.username_claim, "preferred_username")
+    }
+
+    fn groups_claim(&self) -> &str {
+        or_default(&self.groups_claim, "groups")
+    }
+
+    /// The setting under its `oidc_` key, for overrides.
+    pub fn get(&self, key: &str) -> &str {
+        match key {
+            "oidc_issuer" => &self.issuer,
+            "oidc_client_id" => &self.client_id,
+            "oidc_client_secret" => &self.client_secret,
+            "oidc_scopes" => &self.scopes,
+            "oidc_username_claim" => &self.username_claim,
+            "oidc_groups_claim" => &self.groups_claim,
+            "oidc_admin_groups" => &self.admin_groups,
+            "oidc_operator_groups" => &self.operator_groups,
+            "oidc_default_role" => &self.default_role,
+            "oidc_redirect_url" => &self.redirect_url,
+            _ => "",
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        let field = match key {
+            "oidc_issuer" => &mut self.issuer,
+            "oidc_client_id" => &mut self.client_id,
+            "oidc_client_secret" => &mut self.client_secret,
+            "oidc_scopes" => &mut self.scopes,
+            "oidc_username_claim" => &mut self.username_claim,
+            "oidc_groups_claim" => &mut self.groups_claim,
+            "oidc_admin_groups" => &mut self.admin_groups,
+            "oidc_operator_groups" => &mut self.operator_groups,
+            "oidc_default_role" => &mut self.default_role,
+            "oidc_redirect_url" => &mut self.redirect_url,
+            _ => return,
+        };
+        *field = value;
+    }
+}
+
+fn issuer_id(issuer: &str) -> String {
+    issuer.trim().trim_end_matches('/').to_string()
+}
+
+fn or_default<'a>(value: &'a str, default: &'a str) -> &'a str {
+    if value.trim().is_empty() { default } else { value.trim() }
+}
+
+/// The endpoints a provider publishes in its discovery document.
+#[derive(Deserialize)]
+pub struct Provider {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// A login sent to the provider whose callback has not arrived yet.
+struct PendingLogin {
+    verifier: String,
+    nonce: String,
+    redirect_uri: String,
+    next: String,
+    started: Instant,
+}
+
+/// A provider's endpoints and the keys it signs ID tokens with.
+struct ProviderKeys {
+    provider: Provider,
+    keys: JwkSet,
+}
+
+struct CachedProvider {
+    issuer: String,
+    fetched: Instant,
+    entry: Arc<ProviderKeys>,
+}
+
+/// Who the provider signed in.
+pub struct SignIn {
+    /// The provider's issuer and its stable identifier for the user, which accounts are bound to.
+    pub issuer: String,
+    pub subject: String,
+    /// The username claim, used to name a new account.
+    pub username: String,
+    pub groups: Vec<String>,
+    /// Where the browser was headed before it was sent to log in.
+    pub next: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// The relying-party side of the authorization code flow with PKCE. Logins in flight
+/// are kept in memory, so a restart in between simply sends the user to log in again.
+pub struct Oidc {
+    client: reqwest::Client,
+    cache: tokio::sync::Mutex<Option<CachedProvider>>,
+    pending: std::sync::Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl Oidc {
+    pub fn new(client: reqwest::Client) -> Oidc {
+        Oidc { client, cache: Default::default(), pending: Default::default() }
+    }
+
+    /// Fetches the provider's discovery document, e.g. to check the settings before saving them.
+    pub async fn discover(&self, issuer: &str) -> Result<Provider, String> {
+        let url = format!("{}/.well-known/openid-configuration", issuer.trim().trim_end_matches('/'));
+        let provider: Provider = self.fetch_json(&url).await?;
+        // The issuer is compared verbatim to the `iss` claim of every ID token
+        if provider.issuer.trim_end_matches('/') != issuer.trim().trim_end_matches('/') {
+            return Err(format!("{} describes issuer '{}', not '{}'", url, provider.issuer, issuer));
+        }
+        Ok(provider)
+    }
+
+    /// Starts a login: returns the provider URL to send the browser to and the state
+    /// to bind to it in `STATE_COOKIE`.
+    pub async fn begin(&self, settings: &OidcSettings, redirect_uri: String, next: String) -> Result<(String, String), String> {
+        let entry = self.provider(settings, false).await?;
+        let provider = &entry.provider;
+        let state = random_token();
+        let verifier = random_token();
+        let nonce = random_token();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+
+        let mut url = reqwest::Url::parse(&provider.authorization_endpoint)
+            .map_err(|e| format!("Invalid authorization endpoint: {}", e))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &settings.client_id)
+            .append_pair("redirect_uri", &redirect_uri)
+            .append_pair("scope", settings.scopes())
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, login| login.started.elapsed() < LOGIN_TIMEOUT);
+        if pending.len() >= MAX_PENDING {
+            if let Some(oldest) = pending.iter().min_by_key(|(_, login)| login.started).map(|(state, _)| state.clone()) {
+                pending.remove(&oldest);
+            }
+        }
+        pending.insert(state.clone(), PendingLogin { verifier, nonce, redirect_uri, next, started: Instant::now() });
+        Ok((url.to_string(), state))
+    }
+
+    /// Completes the login `state` started: exchanges `code` for an ID token and verifies it.
+    pub async fn finish(&self, settings: &OidcSettings, state: &str, code: &str) -> Result<SignIn, String> {
+        let login = self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(state)
+            .filter(|login| login.started.elapsed() < LOGIN_TIMEOUT)
+            .ok_or("The login expired or was already used; please sign in again")?;
+        let entry = self.provider(settings, false).await?;
+
+        let response = self.client
+            .post(&entry.provider.token_endpoint)
+            .basic_auth(&settings.client_id, Some(&settings.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &login.redirect_uri),
+                ("code_verifier", &login.verifier),
+            ])
+            .send()
+            .await
+            .map_err(|e| format!("Token request failed: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Token request was refused ({}): {}", status, body));
+        }
+        let tokens: TokenResponse = response.json().await.map_err(|e| format!("Invalid token response: {}", e))?;
+
+        let (issuer, claims) = self.verify(settings, &tokens.id_token).await?;
+        if claims.get("nonce").and_then(|v| v.as_str()) != Some(login.nonce.as_str()) {
+            return Err("ID token nonce does not match the login".to_string());
+        }
+        let username = claims.get(settings.username_claim())
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| format!("ID token has no '{}' claim", settings.username_claim()))?;
+        // Providers send groups as an array, though some use a comma-separated string
+        let groups = match claims.get(settings.groups_claim()) {
+            Some(serde_json::Value::Array(groups)) => groups.iter().filter_map(|g| g.as_str()).map(String::from).collect(),
+            Some(serde_json::Value::String(groups)) => groups.split(',').map(|g| g.trim().to_string()).collect(),
+            _ => Vec::new(),
+        };
+        let subject = claims.get("sub")
+            .and_then(|v| v.as_str())
+            .filter(|sub| !sub.is_empty())
+            .ok_or("ID token has no 'sub' claim")?;
+        Ok(SignIn { issuer, subject: subject.to_string(), username: username.to_string(), groups, next: login.next })
+    }
+
+    /// The role `groups` grant under the configured mapping.
+    pub fn role(settings: &OidcSettings, groups: &[String]) -> Result<Option<Role>, String> {
+        Ok(settings.roles()?.role(groups.iter().map(String::as_str)))
+    }
+
+    /// Checks the token's signature against the provider's keys, and its issuer,
+    /// audience and expiry. Returns the issuer and the token's claims.
+    async fn verify(&self, settings: &OidcSettings, id_token: &str) -> Result<(String, serde_json::Map<String, serde_json::Value>), String> {
+        let header = jsonwebtoken::decode_header(id_token).map_err(|e| format!("Invalid ID token: {}", e))?;
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err("ID tokens signed with a shared secret are not supported".to_string());
+        }
+        // A key that is missing or fails to verify may have been rotated since it was cached
+        for refresh in [false, true] {
+            let entry = self.provider(settings, refresh).await?;
+            let jwk = entry.keys.keys.iter().find(|k| header.kid.is_none() || k.common.key_id == header.kid);
+            let Some(jwk) = jwk else { continue };
+            let key = DecodingKey::from_jwk(jwk).map_err(|e| format!("Unusable signing key: {}", e))?;
+
+            let mut validation = Validation::new(header.alg);
+            validation.set_audience(&[&settings.client_id]);
+            validation.set_issuer(&[&entry.provider.issuer]);
+            validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+            match jsonwebtoken::decode(id_token, &key, &validation) {
+                Ok(data) => return Ok((issuer_id(&entry.provider.issuer), data.claims)),
+                Err(e) if !refresh && *e.kind() == ErrorKind::InvalidSignature => continue,
+                Err(e) => return Err(format!("ID token rejected: {}", e)),
+            }
+        }
+        Err("ID token is signed with a key the provider does not publish".to_string())
+    }
+
+    /// Discovery and signing keys for the configured issuer, fetched again once stale,
+    /// when the issuer changed, or when `refresh` asks for it.
+    async fn provider(&self, settings: &OidcSettings, refresh: bool) -> Result<Arc<ProviderKeys>, String> {
+        let mut cache = self.cache.lock().await;
+        let fresh = cache.as_ref().filter(|c| !refresh && c.issuer == settings.issuer && c.fetched.elapsed() < PROVIDER_TTL);
+        if let Some(cached) = fresh {
+            return Ok(cached.entry.clone());
+        }
+        let provider = self.discover(&settings.issuer).await?;
+        let keys = self.fetch_json(&provider.jwks_uri).await?;
+        let entry = Arc::new(ProviderKeys { provider, keys });
+        *cache = Some(CachedProvider { issuer: settings.issuer.clone(), fetched: Instant::now(), entry: entry.clone() });
+        Ok(entry)
+    }
+
+    async fn fetch_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, String> {
+        let response = self.client.get(url).send().await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+        response.json().await.map_err(|e| format!("Invalid response from {}: {}", url, e))
+    }
+}
+
+/// Scoped to the callback, which is the only place that reads it.
+pub fn state_cookie(state: &str, max_age_secs: u64, base_path: &str, secure: bool) -> String {
+    format!(
+        "{}={}; Path={}/api/oidc/; HttpOnly; SameSite=Lax; Max-Age={}{}",
+        STATE_COOKIE, state, base_path, max_age_secs, super::secure_attribute(secure),
+    )
+}
+
+pub fn clear_state_cookie(base_path: &str, secure: bool) -> String {
+    state_cookie("", 0, base_path, secure)
+}
+
+/// Seconds the state cookie lives: as long as the login it belongs to.
+pub fn state_cookie_max_age() -> u64 {
+    LOGIN_TIMEOUT.as_secs()
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Form, State};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    const CLIENT_ID: &str = "dashboard";
+    const CLIENT_SECRET: &str = "secret";
+    const REDIRECT_URI: &str = "https://dashboard.example.com/api/oidc/callback";
+
+    /// An identity provider serving discovery, its signing key and the token endpoint.
+    struct MockIssuer {
+        issuer: String,
+        key: rcgen::KeyPair,
+        /// Codes handed out at authorization, with the PKCE challenge and nonce they were issued for.
+        codes: std::sync::Mutex<HashMap<String, (String, String)>>,
+        /// Claims replacing those of the next ID tokens.
+        claims: std::sync::Mutex<serde_json::Map<String, Value>>,
+        /// Signs ID tokens instead of `key` when set.
+        forger: std::sync::Mutex<Option<rcgen::KeyPair>>,
+    }
+
+    impl MockIssuer {
+        async fn start() -> Arc<MockIssuer> {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let issuer = Arc::new(MockIssuer {
+                issuer: format!("http://{}", listener.local_addr().unwrap()),
+                key: rcgen::KeyPair::generate().unwrap(),
+                codes: Default::default(),
+                claims: Default::default(),
+                forger: Default::default(),
+            });
+            let app = Router::new()
+                .route("/.well-known/openid-configuration", get(discovery))
+                // Describes the root issuer, as a misconfigured issuer URL would
+                .route("/elsewhere/.well-known/openid-configuration", get(discovery))
+                .route("/jwks", get(jwks))
+                .route("/token", post(token))
+                .with_state(issuer.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            issuer
+        }
+
+        fn settings(&self) -> OidcSettings {
+            OidcSettings {
+                issuer: self.issuer.clone(),
+                client_id: CLIENT_ID.to_string(),
+                client_secret: CLIENT_SECRET.to_string(),
+                admin_groups: "media-admins".to_string(),
+                operator_groups: "media-operators".to_string(),
+                default_role: "none".to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn set_claim(&self, name: &str, value: Value) {
+            self.claims.lock().unwrap().insert(name.to_string(), value);
+        }
+
+        /// Plays the browser's round trip through the provider, whose authorization endpoint
+        /// issues `code` for the challenge and nonce `begin` sent along.
+        async fn sign_in(&self, oidc: &Oidc, settings: &OidcSettings) -> Result<SignIn, String> {
+            let (url, state) = oidc.begin(settings, REDIRECT_URI.to_string(), "/".to_string()).await?;
+            let url = reqwest::Url::parse(&url).unwrap();
+            let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            assert_eq!(query["state"], state);
+            assert_eq!(query["code_challenge_method"], "S256");
+            let issued = (query["code_challenge"].clone(), query["nonce"].clone());
+            self.codes.lock().unwrap().insert("code".to_string(), issued);
+            oidc.finish(settings, &state, "code").await
+        }
+    }
+
+    async fn discovery(State(mock): State<Arc<MockIssuer>>) -> Json<Value> {
+        Json(json!({
+            "issuer": mock.issuer,
+            "authorization_endpoint": format!("{}/authorize", mock.issuer),
+            "token_endpoint": format!("{}/token", mock.issuer),
+            "jwks_uri": format!("{}/jwks", mock.issuer),
+        }))
+    }
+
+    async fn jwks(State(mock): State<Arc<MockIssuer>>) -> Json<Value> {
+        // An uncompressed P-256 point: 0x04, then x and y
+        let point = mock.key.public_key_raw();
+        Json(json!({ "keys": [{
+            "kty": "EC",
+            "crv": "P-256",
+            "kid": "signing",
+            "alg": "ES256",
+            "use": "sig",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+        }]}))
+    }
+
+    async fn token(
+        State(mock): State<Arc<MockIssuer>>,
+        headers: HeaderMap,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Result<Json<Value>, (StatusCode, &'static str)> {
+        let credentials = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", CLIENT_ID, CLIENT_SECRET)));
+        if headers.get("authorization").and_then(|v| v.to_str().ok()) != Some(credentials.as_str()) {
+            return Err((StatusCode::UNAUTHORIZED, "invalid_client"));
+        }
+        if form.get("grant_type").map(String::as_str) != Some("authorization_code") || form.get("redirect_uri").map(String::as_str) != Some(REDIRECT_URI) {
+            return Err((StatusCode::BAD_REQUEST, "invalid_request"));
+        }
+        let code = form.get("code").ok_or((StatusCode::BAD_REQUEST, "invalid_request"))?;
+        let (challenge, nonce) = mock.codes.lock().unwrap().remove(code).ok_or((StatusCode::BAD_REQUEST, "invalid_grant"))?;
+        let verifier = form.get("code_verifier").ok_or((StatusCode::BAD_REQUEST, "invalid_grant"))?;
+        if URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) != challenge {
+            return Err((StatusCode::BAD_REQUEST, "invalid_grant"));
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let mut claims = json!({
+            "iss": mock.issuer,
+            "aud": CLIENT_ID,
+            "sub": "subject-1",
+            "iat": now,
+            "exp": now + 300,
+            "nonce": nonce,
+            "preferred_username": "alice",
+            "groups": ["media-operators"],
+        });
+        claims.as_object_mut().unwrap().extend(mock.claims.lock().unwrap().clone());
+        let forger = mock.forger.lock().unwrap();
+        let pem = forger.as_ref().unwrap_or(&mock.key).serialize_pem();
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some("signing".to_string());
+        let id_token = jsonwebtoken::encode(&header, &claims, &EncodingKey::from_ec_pem(pem.as_bytes()).unwrap()).unwrap();
+        Ok(Json(json!({ "id_token": id_token, "token_type": "Bearer" })))
+    }
+
+    fn oidc() -> Oidc {
+        Oidc::new(reqwest::Client::new())
+    }
+
+    #[tokio::test]
+    async fn discovers_the_provider() {
+        let mock = MockIssuer::start().await;
+        let provider = oidc().discover(&format!("{}/", mock.issuer)).await.unwrap();
+        assert_eq!(provider.issuer, mock.issuer);
+        assert_eq!(provider.token_endpoint, format!("{}/token", mock.issuer));
+        assert_eq!(provider.jwks_uri, format!("{}/jwks", mock.issuer));
+
+        let error = oidc().discover(&format!("{}/elsewhere", mock.issuer)).await.err().unwrap();
+        assert!(error.contains("describes issuer"), "{}", error);
+        assert!(oidc().discover(&format!("{}/missing", mock.issuer)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn exchanges_the_code_with_the_pkce_verifier() {
+        let mock = MockIssuer::start().await;
+        let sign_in = mock.sign_in(&oidc(), &mock.settings()).await.unwrap();
+        assert_eq!(sign_in.issuer, mock.issuer);
+        assert_eq!(sign_in.subject, "subject-1");
+        assert_eq!(sign_in.username, "alice");
+        assert_eq!(sign_in.groups, ["media-operators"]);
+        assert_eq!(sign_in.next, "/");
+    }
+
+    #[tokio::test]
+    async fn refuses_a_code_issued_for_another_challenge() {
+        let mock = MockIssuer::start().await;
+        let oidc = oidc();
+        let settings = mock.settings();
+        let (_, state) = oidc.begin(&settings, REDIRECT_URI.to_string(), "/".to_string()).await.unwrap();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(b"another verifier"));
+        mock.codes.lock().unwrap().insert("code".to_string(), (challenge, "nonce".to_string()));
+        let error = oidc.finish(&settings, &state, "code").await.err().unwrap();
+        assert!(error.contains("invalid_grant"), "{}", error);
+        // The login is used up either way
+        assert!(oidc.finish(&settings, &state, "code").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_a_nonce_mismatch() {
+        let mock = MockIssuer::start().await;
+        mock.set_claim("nonce", json!("replayed"));
+        let error = mock.sign_in(&oidc(), &mock.settings()).await.err().unwrap();
+        assert!(error.contains("nonce"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_bad_signature() {
+        let mock = MockIssuer::start().await;
+        *mock.forger.lock().unwrap() = Some(rcgen::KeyPair::generate().unwrap());
+        let error = mock.sign_in(&oidc(), &mock.settings()).await.err().unwrap();
+        assert!(error.contains("InvalidSignature"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rejects_another_audience() {
+        let mock = MockIssuer::start().await;
+        mock.set_claim("aud", json!("another-client"));
+        let error = mock.sign_in(&oidc(), &mock.settings()).await.err().unwrap();
+        assert!(error.contains("InvalidAudience"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rejects_another_issuer() {
+        let mock = MockIssuer::start().await;
+        mock.set_claim("iss", json!("https://impostor.example.com"));
+        let error = mock.sign_in(&oidc(), &mock.settings()).await.err().unwrap();
+        assert!(error.contains("InvalidIssuer"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn maps_groups_to_roles() {
+        let mock = MockIssuer::start().await;
+        let oidc = oidc();
+        let settings = mock.settings();
+        let role = |sign_in: SignIn| Oidc::role(&settings, &sign_in.groups).unwrap();
+
+        mock.set_claim("groups", json!(["users", "media-admins"]));
+        assert_eq!(role(mock.sign_in(&oidc, &settings).await.unwrap()), Some(Role::Admin));
+        // Some providers send a comma-separated string
+        mock.set_claim("groups", json!("users, media-operators"));
+        assert_eq!(role(mock.sign_in(&oidc, &settings).await.unwrap()), Some(Role::Operator));
+        // With the default role "none", no matching group refuses the login
+        mock.set_claim("groups", json!(["users"]));
+        assert_eq!(role(mock.sign_in(&oidc, &settings).await.unwrap()), None);
+        assert_eq!(Oidc::role(&settings, &[]).unwrap(), None);
+
+        let settings = OidcSettings { default_role: String::new(), ..settings };
+        assert_eq!(Oidc::role(&settings, &["users".to_string()]).unwrap(), Some(Role::Viewer));
+        let settings = OidcSettings { default_role: "owner".to_string(), ..settings };
+        assert!(Oidc::role(&settings, &[]).is_err());
+    }
+}
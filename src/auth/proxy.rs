@@ -0,0 +1,150 @@
+use super::{GroupRoles, Role};
+use axum::http::{HeaderMap, HeaderName};
+use std::net::IpAddr;
+
+/// A network in CIDR notation, e.g. `172.16.0.0/12`; a bare address is a single host.
+struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    fn parse(value: &str) -> Result<Cidr, String> {
+        let invalid = || format!("'{}' is not an IP address or CIDR range", value);
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix.parse::<u8>().map_err(|_| invalid())?)),
+            None => (value, None),
+        };
+        let network: IpAddr = address.parse().map_err(|_| invalid())?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Cidr { network, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        // Dual-stack listeners report IPv4 peers as IPv4-mapped IPv6 addresses
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            v4 => v4,
+        };
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The reverse proxies listed in `proxy_auth_trusted_proxies`, whose forwarding headers
+/// are believed. Empty when none are configured.
+#[derive(Default)]
+pub struct TrustedProxies(Vec<Cidr>);
+
+impl TrustedProxies {
+    pub fn from_settings<'a>(setting: impl Fn(&str) -> Option<&'a str>) -> Result<TrustedProxies, String> {
+        setting("proxy_auth_trusted_proxies")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|cidr| !cidr.is_empty())
+            .map(|cidr| Cidr::parse(cidr).map_err(|e| format!("proxy_auth_trusted_proxies: {}", e)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(TrustedProxies)
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// The client a request from `peer` was made by: the last `X-Forwarded-For` hop before
+    /// the trusted proxies, or `peer` itself when it is not one of them.
+    pub fn client(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let hops: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+        let mut client = peer;
+        for hop in hops.iter().rev() {
+            if !self.contains(client) {
+                break;
+            }
+            match hop.trim().parse() {
+                Ok(ip) => client = ip,
+                Err(_) => break,
+            }
+        }
+        client
+    }
+}
+
+/// Forward authentication: an authenticating proxy such as Authelia or Authentik signs the
+/// user in and passes their name (and groups) in headers. The headers are only believed on
+/// connections from the trusted proxies; anyone else could set them.
+pub struct ProxyAuth {
+    user_header: HeaderName,
+    groups_header: HeaderName,
+    trusted: TrustedProxies,
+    roles: GroupRoles,
+}
+
+impl ProxyAuth {
+    /// Built from the `proxy_auth_*` startup settings; `None` unless a user header is set.
+    pub fn from_settings<'a>(setting: impl Fn(&str) -> Option<&'a str>) -> Result<Option<ProxyAuth>, String> {
+        let Some(user_header) = setting("proxy_auth_header") else {
+            return Ok(None);
+        };
+        let header = |name: &str, value: &str| {
+            HeaderName::try_from(value.trim()).map_err(|_| format!("{}: '{}' is not a valid header name", name, value))
+        };
+        let trusted = TrustedProxies::from_settings(&setting)?;
+        if trusted.0.is_empty() {
+            return Err("proxy_auth_header needs proxy_auth_trusted_proxies, the addresses of the proxies allowed to set it".to_string());
+        }
+        let roles = GroupRoles::parse(
+            setting("proxy_auth_admin_groups").unwrap_or_default(),
+            setting("proxy_auth_operator_groups").unwrap_or_default(),
+            setting("proxy_auth_default_role").unwrap_or("viewer"),
+        )
+        .map_err(|e| format!("proxy_auth_default_role: {}", e))?;
+
+        Ok(Some(ProxyAuth {
+            user_header: header("proxy_auth_header", user_header)?,
+            groups_header: header("proxy_auth_groups_header", setting("proxy_auth_groups_header").unwrap_or("Remote-Groups"))?,
+            trusted,
+            roles,
+        }))
+    }
+
+    /// The user the proxy vouches for, if the request came from a trusted proxy.
+    pub fn user<'h>(&self, ip: IpAddr, headers: &'h HeaderMap) -> Option<&'h str> {
+        if !self.trusted.contains(ip) {
+            return None;
+        }
+        headers
+            .get(&self.user_header)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|user| !user.is_empty())
+    }
+
+    /// The role granted by the groups the proxy reports, falling back to the default role.
+    pub fn role_from_groups(&self, headers: &HeaderMap) -> Option<Role> {
+        let groups = headers
+            .get_all(&self.groups_header)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        self.roles.role(groups)
+    }
+}
@@ -0,0 +1,83 @@
+use super::Role;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+/// Marks a string as a dashboard API token, so it is recognizable in scripts and secret scanners.
+const TOKEN_PREFIX: &str = "mdt_";
+/// The longest expiry a token may be given; a token that should outlive it never expires.
+pub const MAX_EXPIRY_DAYS: u32 = 3650;
+
+/// What an API token may be used for. A token never grants more than its owner's role.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Scope {
+    /// Status, uptime, calendar, statistics and the libraries.
+    #[serde(rename = "status:read")]
+    StatusRead,
+    /// Searching for, adding and removing series and movies.
+    #[serde(rename = "media:write")]
+    MediaWrite,
+    /// Adding, removing, starting and stopping torrents.
+    #[serde(rename = "torrents:control")]
+    TorrentsControl,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::StatusRead, Scope::MediaWrite, Scope::TorrentsControl];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::StatusRead => "status:read",
+            Scope::MediaWrite => "media:write",
+            Scope::TorrentsControl => "torrents:control",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Scope> {
+        Scope::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+
+    /// The role the token's owner needs for the scope to be granted.
+    pub fn min_role(self) -> Role {
+        match self {
+            Scope::StatusRead => Role::Viewer,
+            Scope::MediaWrite | Scope::TorrentsControl => Role::Operator,
+        }
+    }
+}
+
+/// Scopes as stored in `api_tokens.scopes`: space-separated, unknown names skipped.
+pub fn parse_scopes(stored: &str) -> Vec<Scope> {
+    stored.split_whitespace().filter_map(Scope::parse).collect()
+}
+
+pub fn format_scopes(scopes: &[Scope]) -> String {
+    scopes.iter().map(|scope| scope.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+/// The token a request authenticated with, attached to its `Identity`.
+#[derive(Clone, serde::Serialize)]
+pub struct TokenGrant {
+    pub id: i64,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// A fresh random token, shown to its owner once.
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// The key a token is stored under. Tokens are random, so a plain digest is as good as a
+/// password hash here and keeps the lookup on every request cheap.
+pub fn token_hash(token: &str) -> String {
+    super::session_id(token)
+}
+
+/// The token in an `Authorization: Bearer` header, if that is what the header carries.
+pub fn read_bearer(header: &str) -> Option<&str> {
+    let (scheme, token) = header.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim()).filter(|token| !token.is_empty())
+}
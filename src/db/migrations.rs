@@ -0,0 +1,150 @@
+use sqlx::SqlitePool;
+
+/// A numbered schema change, embedded in the binary. Never edit one that has been
+/// released; add a new one instead.
+struct Migration {
+    version: i64,
+    name: &'static str,
+    sql: &'static str,
+    /// Drops or rewrites data, so the database file is backed up before it runs.
+    destructive: bool,
+}
+
+/// Every migration in version order. The last entry is the newest schema this build understands.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        sql: include_str!("migrations/001_baseline.sql"),
+        destructive: false,
+    },
+    Migration {
+        version: 2,
+        name: "services",
+        sql: include_str!("migrations/002_services.sql"),
+        destructive: false,
+    },
+    Migration {
+        version: 3,
+        name: "oidc",
+        sql: include_str!("migrations/003_oidc.sql"),
+        destructive: false,
+    },
+    Migration {
+        version: 4,
+        name: "api_tokens",
+        sql: include_str!("migrations/004_api_tokens.sql"),
+        destructive: false,
+    },
+    Migration {
+        version: 5,
+        name: "audit_fields",
+        sql: include_str!("migrations/005_audit_fields.sql"),
+        destructive: false,
+    },
+    Migration {
+        version: 6,
+        name: "oidc_subject",
+        sql: include_str!("migrations/006_oidc_subject.sql"),
+        destructive: false,
+    },
+];
+
+/// Brings the schema up to the newest version, one transaction per migration.
+/// Refuses to touch a database written by a newer build.
+pub async fn run(pool: &SqlitePool, database_path: &str) -> Result<(), String> {
+    let latest = MIGRATIONS.last().map_or(0, |m| m.version);
+    let current = current_version(pool).await.map_err(|e| format!("Failed to read schema version: {}", e))?;
+    if current > latest {
+        return Err(format!(
+            "Database schema is at version {}, but this build only understands up to {}. Upgrade media-dashboard or restore a backup.",
+            current, latest
+        ));
+    }
+
+    if current == 0 {
+        // Unversioned databases from before per-user auditing lack this column; the
+        // error on newer ones (or on a fresh file without the table) is expected
+        let _ = sqlx::query("ALTER TABLE audit_logs ADD COLUMN username TEXT;").execute(pool).await;
+    }
+
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+    if current > 0 && pending.iter().any(|m| m.destructive) {
+        backup(pool, database_path, current).await?;
+    }
+
+    for migration in pending {
+        apply(pool, migration)
+            .await
+            .map_err(|e| format!("Migration {} ({}) failed: {}", migration.version, migration.name, e))?;
+        tracing::info!("Applied database migration {} ({})", migration.version, migration.name);
+    }
+    Ok(())
+}
+
+/// The highest applied version. Databases versioned before `schema_version` existed only
+/// carry `PRAGMA user_version`, which is copied over on first run.
+async fn current_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );"
+    )
+    .execute(pool)
+    .await?;
+
+    let recorded: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    if let Some(version) = recorded {
+        return Ok(version);
+    }
+
+    let user_version: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(pool).await?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version <= user_version) {
+        record(pool, migration).await?;
+    }
+    Ok(user_version)
+}
+
+async fn apply(pool: &SqlitePool, migration: &Migration) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::raw_sql(migration.sql).execute(&mut *tx).await?;
+    record(&mut *tx, migration).await?;
+    // Kept in step so older builds, which only read `user_version`, still see the schema moved on
+    sqlx::raw_sql(&format!("PRAGMA user_version = {}", migration.version))
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+async fn record<'e, E>(executor: E, migration: &Migration) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    sqlx::query("INSERT OR IGNORE INTO schema_version (version, name) VALUES (?, ?)")
+        .bind(migration.version)
+        .bind(migration.name)
+        .execute(executor)
+        .await
+        .map(|_| ())
+}
+
+/// Writes a consistent copy of the database next to it before data is dropped.
+async fn backup(pool: &SqlitePool, database_path: &str, version: i64) -> Result<(), String> {
+    let path = format!(
+        "{}.v{}-{}.bak",
+        database_path,
+        version,
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    );
+    sqlx::query("VACUUM INTO ?")
+        .bind(&path)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to back up the database to {}: {}", path, e))?;
+    tracing::info!("Backed up database schema version {} to {}", version, path);
+    Ok(())
+}
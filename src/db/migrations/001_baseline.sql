@@ -0,0 +1,53 @@
+-- The tables init_db created before the schema was versioned.
+CREATE TABLE IF NOT EXISTS audit_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    service TEXT NOT NULL,
+    action TEXT NOT NULL,
+    details TEXT NOT NULL,
+    username TEXT
+);
+
+CREATE TABLE IF NOT EXISTS login_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    username TEXT NOT NULL,
+    ip_address TEXT NOT NULL,
+    success BOOLEAN NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS dashboard_settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS service_checks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    service TEXT NOT NULL,
+    active BOOLEAN NOT NULL,
+    timed_out BOOLEAN NOT NULL DEFAULT 0,
+    latency_ms INTEGER,
+    message TEXT NOT NULL,
+    version TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_service_checks_service_time ON service_checks (service, timestamp);
+
+CREATE INDEX IF NOT EXISTS idx_service_checks_time ON service_checks (timestamp);
+
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    role TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    username TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    user_agent TEXT
+);
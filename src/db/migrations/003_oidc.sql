@@ -0,0 +1,2 @@
+-- Accounts created by OpenID Connect sign-in have their role kept in sync with their groups.
+ALTER TABLE users ADD COLUMN managed_by TEXT NOT NULL DEFAULT 'dashboard';
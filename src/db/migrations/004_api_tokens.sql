@@ -0,0 +1,15 @@
+-- Personal API tokens, stored as digests, and the token behind each audited action.
+CREATE TABLE api_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME,
+    last_used_at DATETIME
+);
+
+CREATE INDEX idx_api_tokens_username ON api_tokens (username);
+
+ALTER TABLE audit_logs ADD COLUMN api_token TEXT;
@@ -0,0 +1,26 @@
+-- Structured audit entries: what an action targeted, the state before and after it, and
+-- whether it succeeded; plus a full-text index over the free text, kept in step by triggers.
+ALTER TABLE audit_logs ADD COLUMN target_id TEXT;
+ALTER TABLE audit_logs ADD COLUMN target_title TEXT;
+ALTER TABLE audit_logs ADD COLUMN before_state TEXT;
+ALTER TABLE audit_logs ADD COLUMN after_state TEXT;
+ALTER TABLE audit_logs ADD COLUMN outcome TEXT NOT NULL DEFAULT 'success';
+
+CREATE INDEX idx_audit_logs_timestamp ON audit_logs (timestamp);
+
+CREATE VIRTUAL TABLE audit_logs_fts USING fts5 (
+    details,
+    target_title,
+    content = 'audit_logs',
+    content_rowid = 'id'
+);
+
+INSERT INTO audit_logs_fts (audit_logs_fts) VALUES ('rebuild');
+
+CREATE TRIGGER audit_logs_fts_insert AFTER INSERT ON audit_logs BEGIN
+    INSERT INTO audit_logs_fts (rowid, details, target_title) VALUES (new.id, new.details, new.target_title);
+END;
+
+CREATE TRIGGER audit_logs_fts_delete AFTER DELETE ON audit_logs BEGIN
+    INSERT INTO audit_logs_fts (audit_logs_fts, rowid, details, target_title) VALUES ('delete', old.id, old.details, old.target_title);
+END;
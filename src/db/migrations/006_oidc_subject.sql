@@ -0,0 +1,5 @@
+-- Single sign-on accounts are bound to the provider's stable subject identifier rather
+-- than to a username claim, which the user may be able to change.
+ALTER TABLE users ADD COLUMN oidc_issuer TEXT;
+ALTER TABLE users ADD COLUMN oidc_subject TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_oidc_subject ON users(oidc_issuer, oidc_subject) WHERE oidc_subject IS NOT NULL;
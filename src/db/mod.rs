@@ -1,9 +1,19 @@
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use std::collections::HashMap;
 use std::env;
+use crate::api::ServiceStatus;
 
-pub async fn init_db() -> SqlitePool {
-    let database_path = env::var("DATABASE_PATH")
-        .unwrap_or_else(|_| "/app/data/media_dashboard.db".to_string());
+mod migrations;
+
+/// Format SQLite's `CURRENT_TIMESTAMP` uses, so bound cutoffs compare correctly as text.
+const SQLITE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub fn database_path() -> String {
+    env::var("DATABASE_PATH").unwrap_or_else(|_| "/app/data/media_dashboard.db".to_string())
+}
+
+pub async fn init_db() -> Result<SqlitePool, String> {
+    let database_path = database_path();
 
     // Ensure the data directory exists — try absolute path first (container), then relative (local dev)
     let _ = tokio::fs::create_dir_all("/app/data").await;
@@ -17,45 +27,11 @@ pub async fn init_db() -> SqlitePool {
         .max_connections(5)
         .connect_with(opts)
         .await
-        .expect("Failed to connect to database");
-
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS audit_logs (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
-            service TEXT NOT NULL,
-            action TEXT NOT NULL,
-            details TEXT NOT NULL
-        );"
-    )
-    .execute(&pool)
-    .await
-    .expect("Failed to create audit_logs table");
-
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS login_events (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
-            username TEXT NOT NULL,
-            ip_address TEXT NOT NULL,
-            success BOOLEAN NOT NULL
-        );"
-    )
-    .execute(&pool)
-    .await
-    .expect("Failed to create login_events table");
+        .map_err(|e| format!("Failed to open {}: {}", database_path, e))?;
 
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS dashboard_settings (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL
-        );"
-    )
-    .execute(&pool)
-    .await
-    .expect("Failed to create dashboard_settings table");
+    migrations::run(&pool, &database_path).await?;
 
-    pool
+    Ok(pool)
 }
 
 pub async fn set_setting(pool: &SqlitePool, key: &str, value: &str) {
@@ -66,6 +42,33 @@ pub async fn set_setting(pool: &SqlitePool, key: &str, value: &str) {
         .await;
 }
 
+pub async fn delete_setting(pool: &SqlitePool, key: &str) {
+    let _ = sqlx::query("DELETE FROM dashboard_settings WHERE key = ?")
+        .bind(key)
+        .execute(pool)
+        .await;
+}
+
+/// Every `dashboard_settings` row, read in one query.
+pub async fn all_settings(pool: &SqlitePool) -> HashMap<String, String> {
+    sqlx::query_as::<_, (String, String)>("SELECT key, value FROM dashboard_settings")
+        .fetch_all(pool)
+        .await
+        .map(|rows| rows.into_iter().collect())
+        .unwrap_or_default()
+}
+
+pub async fn delete_settings(pool: &SqlitePool, keys: &[String]) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for key in keys {
+        sqlx::query("DELETE FROM dashboard_settings WHERE key = ?")
+            .bind(key)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await
+}
+
 pub async fn get_setting(pool: &SqlitePool, key: &str) -> Option<String> {
     sqlx::query_scalar::<_, String>("SELECT value FROM dashboard_settings WHERE key = ?")
         .bind(key)
@@ -74,25 +77,227 @@ pub async fn get_setting(pool: &SqlitePool, key: &str) -> Option<String> {
         .unwrap_or(None)
 }
 
-#[derive(serde::Serialize, sqlx::FromRow)]
+/// One configured service instance. `credentials` and `options` are JSON objects keyed by
+/// field name without the service prefix, e.g. `{"key": "enc:v1:..."}` and `{"timeout": "5"}`.
+#[derive(sqlx::FromRow, Clone)]
+pub struct ServiceRow {
+    pub id: String,
+    pub kind: String,
+    pub name: String,
+    pub base_url: String,
+    pub auth_type: String,
+    pub credentials: String,
+    pub enabled: bool,
+    pub options: String,
+    pub position: i64,
+}
+
+pub async fn list_services(pool: &SqlitePool) -> Result<Vec<ServiceRow>, sqlx::Error> {
+    sqlx::query_as::<_, ServiceRow>(
+        "SELECT id, kind, name, base_url, auth_type, credentials, enabled, options, position
+         FROM services ORDER BY position, id"
+    )
+        .fetch_all(pool)
+        .await
+}
+
+/// Writes `settings` and replaces every service row in one transaction, so a saved
+/// configuration (or one re-encrypted under a new key) never leaves a mix of old and new.
+pub async fn save_config(pool: &SqlitePool, settings: &[(String, String)], rows: &[ServiceRow]) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for (key, value) in settings {
+        sqlx::query("INSERT OR REPLACE INTO dashboard_settings (key, value) VALUES (?, ?)")
+            .bind(key)
+            .bind(value)
+            .execute(&mut *tx)
+            .await?;
+    }
+    sqlx::query("DELETE FROM services").execute(&mut *tx).await?;
+    for row in rows {
+        sqlx::query(
+            "INSERT INTO services (id, kind, name, base_url, auth_type, credentials, enabled, options, position)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+            .bind(&row.id)
+            .bind(&row.kind)
+            .bind(&row.name)
+            .bind(&row.base_url)
+            .bind(&row.auth_type)
+            .bind(&row.credentials)
+            .bind(row.enabled)
+            .bind(&row.options)
+            .bind(row.position)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await
+}
+
+#[derive(serde::Serialize, sqlx::FromRow, Clone)]
 pub struct AuditLog {
     pub id: i64,
     pub timestamp: String,
     pub service: String,
     pub action: String,
     pub details: String,
+    /// Dashboard user who performed the action; `None` for system events.
+    pub username: Option<String>,
+    /// Name of the API token the action was performed with, if any.
+    pub api_token: Option<String>,
+    /// Id of the series, movie, torrent or user the action was performed on.
+    pub target_id: Option<String>,
+    /// Name of the target, e.g. the series title, when it is known.
+    pub target_title: Option<String>,
+    /// The target's relevant state before and after the action. Secrets are masked.
+    #[sqlx(rename = "before_state")]
+    pub before: Option<sqlx::types::Json<serde_json::Value>>,
+    #[sqlx(rename = "after_state")]
+    pub after: Option<sqlx::types::Json<serde_json::Value>>,
+    /// `success` or `failure`.
+    pub outcome: String,
 }
 
-pub async fn log_event(pool: &SqlitePool, service: &str, action: &str, details: &str) {
-    let _ = sqlx::query("INSERT INTO audit_logs (service, action, details) VALUES (?, ?, ?)")
-        .bind(service)
-        .bind(action)
-        .bind(details)
-        .execute(pool)
-        .await;
+const AUDIT_COLUMNS: &str =
+    "id, timestamp, service, action, details, username, api_token, target_id, target_title, before_state, after_state, outcome";
+
+/// An audit entry to record. `new` is enough for dashboard-wide events; actions on one
+/// series, movie, torrent or user also name their target and, where it changed, its state.
+pub struct AuditEvent {
+    service: String,
+    action: String,
+    details: String,
+    target_id: Option<String>,
+    target_title: Option<String>,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+    outcome: &'static str,
+}
+
+impl AuditEvent {
+    pub fn new(service: &str, action: &str, details: impl Into<String>) -> AuditEvent {
+        AuditEvent {
+            service: service.to_string(),
+            action: action.to_string(),
+            details: details.into(),
+            target_id: None,
+            target_title: None,
+            before: None,
+            after: None,
+            outcome: "success",
+        }
+    }
+
+    pub fn target(mut self, id: impl ToString) -> AuditEvent {
+        self.target_id = Some(id.to_string());
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> AuditEvent {
+        self.target_title = Some(title.to_string());
+        self
+    }
+
+    pub fn before(mut self, state: serde_json::Value) -> AuditEvent {
+        self.before = Some(state);
+        self
+    }
+
+    pub fn after(mut self, state: serde_json::Value) -> AuditEvent {
+        self.after = Some(state);
+        self
+    }
+
+    /// Records the action as failed, with `details` saying what was attempted and why it failed.
+    pub fn failed(mut self, details: impl Into<String>) -> AuditEvent {
+        self.details = details.into();
+        self.outcome = "failure";
+        self
+    }
+}
+
+/// Records a system audit event and returns the stored row, or `None` if the insert failed.
+pub async fn log_event(pool: &SqlitePool, username: Option<&str>, service: &str, action: &str, details: &str) -> Option<AuditLog> {
+    record_event(pool, username, None, &AuditEvent::new(service, action, details)).await
+}
+
+/// Records `event` as performed by `username`, with the API token named `api_token` if any.
+pub async fn record_event(pool: &SqlitePool, username: Option<&str>, api_token: Option<&str>, event: &AuditEvent) -> Option<AuditLog> {
+    sqlx::query_as::<_, AuditLog>(&format!(
+        "INSERT INTO audit_logs (username, api_token, service, action, details, target_id, target_title, before_state, after_state, outcome)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         RETURNING {}",
+        AUDIT_COLUMNS
+    ))
+        .bind(username)
+        .bind(api_token)
+        .bind(&event.service)
+        .bind(&event.action)
+        .bind(&event.details)
+        .bind(&event.target_id)
+        .bind(&event.target_title)
+        .bind(event.before.as_ref().map(sqlx::types::Json))
+        .bind(event.after.as_ref().map(sqlx::types::Json))
+        .bind(event.outcome)
+        .fetch_one(pool)
+        .await
+        .ok()
+}
+
+/// Narrows an audit log query. Every criterion is optional and they all have to match.
+#[derive(Default, Clone)]
+pub struct AuditFilter {
+    pub service: Option<String>,
+    pub action: Option<String>,
+    pub username: Option<String>,
+    pub outcome: Option<String>,
+    /// Inclusive lower bound of the timestamp.
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    /// Exclusive upper bound of the timestamp.
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    /// Words that must all appear in the details or target title, each as a word prefix.
+    pub search: Option<String>,
+}
+
+/// Newest entries first, at most `limit` of them, starting below the id `before` when paging on.
+pub async fn query_audit_logs(pool: &SqlitePool, filter: &AuditFilter, before: Option<i64>, limit: i64) -> Result<Vec<AuditLog>, sqlx::Error> {
+    let mut query = sqlx::QueryBuilder::<sqlx::Sqlite>::new(format!("SELECT {} FROM audit_logs WHERE 1 = 1", AUDIT_COLUMNS));
+    let exact = [
+        ("service", &filter.service),
+        ("action", &filter.action),
+        ("username", &filter.username),
+        ("outcome", &filter.outcome),
+    ];
+    for (column, value) in exact {
+        if let Some(value) = value {
+            query.push(format!(" AND {} = ", column)).push_bind(value.as_str());
+        }
+    }
+    if let Some(from) = filter.from {
+        query.push(" AND timestamp >= ").push_bind(from.format(SQLITE_TIME_FORMAT).to_string());
+    }
+    if let Some(to) = filter.to {
+        query.push(" AND timestamp < ").push_bind(to.format(SQLITE_TIME_FORMAT).to_string());
+    }
+    if let Some(terms) = filter.search.as_deref().and_then(fts_query) {
+        query.push(" AND id IN (SELECT rowid FROM audit_logs_fts WHERE audit_logs_fts MATCH ").push_bind(terms).push(")");
+    }
+    if let Some(before) = before {
+        query.push(" AND id < ").push_bind(before);
+    }
+    query.push(" ORDER BY id DESC LIMIT ").push_bind(limit);
+    query.build_query_as::<AuditLog>().fetch_all(pool).await
+}
+
+/// Turns free text into an FTS5 query that matches every word as a prefix. Each word is
+/// quoted, so FTS5 operators and punctuation typed into a search box are taken literally.
+fn fts_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
 }
 
-#[allow(dead_code)]
 pub async fn log_login(pool: &SqlitePool, username: &str, ip_address: &str, success: bool) {
     let _ = sqlx::query("INSERT INTO login_events (username, ip_address, success) VALUES (?, ?, ?)")
         .bind(username)
@@ -101,3 +306,518 @@ pub async fn log_login(pool: &SqlitePool, username: &str, ip_address: &str, succ
         .execute(pool)
         .await;
 }
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+pub struct LoginEvent {
+    pub id: i64,
+    pub timestamp: String,
+    pub username: String,
+    pub ip_address: String,
+    pub success: bool,
+}
+
+pub async fn recent_logins(pool: &SqlitePool, limit: i64) -> Result<Vec<LoginEvent>, sqlx::Error> {
+    sqlx::query_as::<_, LoginEvent>(
+        "SELECT id, timestamp, username, ip_address, success FROM login_events ORDER BY id DESC LIMIT ?"
+    )
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+}
+
+// ===================== Retention =====================
+
+/// A table of history that retention policies trim.
+#[derive(Clone, Copy)]
+pub enum History {
+    AuditLogs,
+    LoginEvents,
+}
+
+impl History {
+    pub fn table(self) -> &'static str {
+        match self {
+            History::AuditLogs => "audit_logs",
+            History::LoginEvents => "login_events",
+        }
+    }
+}
+
+/// The rows of a history table a retention policy expires: written before `before`, or
+/// with an id up to `up_to`, i.e. older than the newest rows it keeps.
+#[derive(Clone, Copy)]
+pub struct Expiry {
+    pub before: Option<chrono::DateTime<chrono::Utc>>,
+    pub up_to: Option<i64>,
+}
+
+impl Expiry {
+    fn push_condition(&self, query: &mut sqlx::QueryBuilder<'_, sqlx::Sqlite>) {
+        query.push(" AND (0");
+        if let Some(before) = self.before {
+            query.push(" OR timestamp < ").push_bind(before.format(SQLITE_TIME_FORMAT).to_string());
+        }
+        if let Some(up_to) = self.up_to {
+            query.push(" OR id <= ").push_bind(up_to);
+        }
+        query.push(")");
+    }
+}
+
+/// The id of the newest row of `history` beyond its newest `keep` rows, if it has more.
+pub async fn history_beyond(pool: &SqlitePool, history: History, keep: u64) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar(&format!("SELECT id FROM {} ORDER BY id DESC LIMIT 1 OFFSET ?", history.table()))
+        .bind(keep as i64)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Up to `limit` expired audit entries with ids above `after`, oldest first.
+pub async fn expired_audit_logs(pool: &SqlitePool, expiry: Expiry, after: i64, limit: i64) -> Result<Vec<AuditLog>, sqlx::Error> {
+    let mut query = sqlx::QueryBuilder::new(format!("SELECT {} FROM audit_logs WHERE id > ", AUDIT_COLUMNS));
+    query.push_bind(after);
+    expiry.push_condition(&mut query);
+    query.push(" ORDER BY id LIMIT ").push_bind(limit);
+    query.build_query_as().fetch_all(pool).await
+}
+
+/// Up to `limit` expired login events with ids above `after`, oldest first.
+pub async fn expired_login_events(pool: &SqlitePool, expiry: Expiry, after: i64, limit: i64) -> Result<Vec<LoginEvent>, sqlx::Error> {
+    let mut query = sqlx::QueryBuilder::new("SELECT id, timestamp, username, ip_address, success FROM login_events WHERE id > ");
+    query.push_bind(after);
+    expiry.push_condition(&mut query);
+    query.push(" ORDER BY id LIMIT ").push_bind(limit);
+    query.build_query_as().fetch_all(pool).await
+}
+
+/// Deletes the expired rows of `history`, returning how many were removed.
+pub async fn delete_expired(pool: &SqlitePool, history: History, expiry: Expiry) -> Result<u64, sqlx::Error> {
+    let mut query = sqlx::QueryBuilder::new(format!("DELETE FROM {} WHERE 1 = 1", history.table()));
+    expiry.push_condition(&mut query);
+    query.build().execute(pool).await.map(|result| result.rows_affected())
+}
+
+// ===================== Users =====================
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub role: String,
+    pub created_at: String,
+    /// "dashboard" for accounts managed here, "oidc" for ones created by single sign-on,
+    /// whose role follows their groups at every login.
+    pub managed_by: String,
+    /// The OIDC provider the account signs in through. Without `oidc_subject` the account
+    /// awaits its first single sign-on, which links it.
+    pub oidc_issuer: Option<String>,
+    pub oidc_subject: Option<String>,
+}
+
+const USER_COLUMNS: &str = "id, username, password_hash, role, created_at, managed_by, oidc_issuer, oidc_subject";
+
+pub async fn count_users(pool: &SqlitePool) -> i64 {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users")
+        .fetch_one(pool)
+        .await
+        .unwrap_or(0)
+}
+
+pub async fn count_admins(pool: &SqlitePool) -> i64 {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE role = 'admin'")
+        .fetch_one(pool)
+        .await
+        .unwrap_or(0)
+}
+
+pub async fn list_users(pool: &SqlitePool) -> Result<Vec<User>, sqlx::Error> {
+    sqlx::query_as::<_, User>(&format!("SELECT {} FROM users ORDER BY username", USER_COLUMNS))
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn find_user(pool: &SqlitePool, username: &str) -> Option<User> {
+    sqlx::query_as::<_, User>(&format!("SELECT {} FROM users WHERE username = ?", USER_COLUMNS))
+        .bind(username)
+        .fetch_optional(pool)
+        .await
+        .unwrap_or(None)
+}
+
+pub async fn find_user_by_id(pool: &SqlitePool, id: i64) -> Option<User> {
+    sqlx::query_as::<_, User>(&format!("SELECT {} FROM users WHERE id = ?", USER_COLUMNS))
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .unwrap_or(None)
+}
+
+pub async fn create_user(pool: &SqlitePool, username: &str, password_hash: &str, role: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO users (username, password_hash, role) VALUES (?, ?, ?)")
+        .bind(username)
+        .bind(password_hash)
+        .bind(role)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+/// The account bound to the OIDC provider's `subject` at `issuer`.
+pub async fn find_oidc_user(pool: &SqlitePool, issuer: &str, subject: &str) -> Option<User> {
+    sqlx::query_as::<_, User>(&format!("SELECT {} FROM users WHERE oidc_issuer = ? AND oidc_subject = ?", USER_COLUMNS))
+        .bind(issuer)
+        .bind(subject)
+        .fetch_optional(pool)
+        .await
+        .unwrap_or(None)
+}
+
+/// Creates an account for someone the OIDC provider signed in, bound to their subject.
+/// Returns false if the username is taken.
+pub async fn create_oidc_user(pool: &SqlitePool, username: &str, password_hash: &str, role: &str, issuer: &str, subject: &str) -> bool {
+    sqlx::query(
+        "INSERT OR IGNORE INTO users (username, password_hash, role, managed_by, oidc_issuer, oidc_subject)
+         VALUES (?, ?, ?, 'oidc', ?, ?)"
+    )
+        .bind(username)
+        .bind(password_hash)
+        .bind(role)
+        .bind(issuer)
+        .bind(subject)
+        .execute(pool)
+        .await
+        .is_ok_and(|r| r.rows_affected() > 0)
+}
+
+/// Binds an account to `subject` at `issuer` if it awaits its link to that provider, or was
+/// created by single sign-on before subjects were recorded. Returns false otherwise.
+pub async fn bind_oidc_subject(pool: &SqlitePool, id: i64, issuer: &str, subject: &str) -> bool {
+    sqlx::query(
+        "UPDATE users SET oidc_issuer = ?, oidc_subject = ?
+         WHERE id = ? AND oidc_subject IS NULL AND (oidc_issuer = ? OR (oidc_issuer IS NULL AND managed_by = 'oidc'))"
+    )
+        .bind(issuer)
+        .bind(subject)
+        .bind(id)
+        .bind(issuer)
+        .execute(pool)
+        .await
+        .is_ok_and(|r| r.rows_affected() > 0)
+}
+
+/// Lets the next single sign-on at `issuer` under the account's name link it, or with
+/// `None` removes its link so it no longer signs in through the provider.
+pub async fn set_oidc_link(pool: &SqlitePool, id: i64, issuer: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET oidc_issuer = ?, oidc_subject = NULL WHERE id = ?")
+        .bind(issuer)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+/// Creates the initial admin account, unless any user already exists. Returns whether it was created.
+pub async fn create_first_user(pool: &SqlitePool, username: &str, password_hash: &str) -> bool {
+    sqlx::query(
+        "INSERT INTO users (username, password_hash, role)
+         SELECT ?, ?, 'admin' WHERE NOT EXISTS (SELECT 1 FROM users)"
+    )
+        .bind(username)
+        .bind(password_hash)
+        .execute(pool)
+        .await
+        .is_ok_and(|r| r.rows_affected() > 0)
+}
+
+pub async fn set_user_role(pool: &SqlitePool, id: i64, role: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET role = ? WHERE id = ?")
+        .bind(role)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+/// Replaces a user's password hash, but only if it still equals `previous` (when given),
+/// so concurrent updates do not overwrite each other.
+pub async fn set_user_password(pool: &SqlitePool, username: &str, password_hash: &str, previous: Option<&str>) -> bool {
+    let result = match previous {
+        Some(previous) => sqlx::query("UPDATE users SET password_hash = ? WHERE username = ? AND password_hash = ?")
+            .bind(password_hash)
+            .bind(username)
+            .bind(previous)
+            .execute(pool)
+            .await,
+        None => sqlx::query("UPDATE users SET password_hash = ? WHERE username = ?")
+            .bind(password_hash)
+            .bind(username)
+            .execute(pool)
+            .await,
+    };
+    result.is_ok_and(|r| r.rows_affected() > 0)
+}
+
+pub async fn delete_user(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM users WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+// ===================== Sessions =====================
+
+#[derive(sqlx::FromRow)]
+pub struct Session {
+    pub username: String,
+    pub role: String,
+}
+
+/// Stores a login session under the digest of its cookie token. Returns false if the insert failed.
+pub async fn create_session(
+    pool: &SqlitePool,
+    id: &str,
+    username: &str,
+    expires_at: chrono::DateTime<chrono::Utc>,
+    user_agent: Option<&str>,
+) -> bool {
+    sqlx::query("INSERT INTO sessions (id, username, expires_at, user_agent) VALUES (?, ?, ?, ?)")
+        .bind(id)
+        .bind(username)
+        .bind(expires_at.format(SQLITE_TIME_FORMAT).to_string())
+        .bind(user_agent)
+        .execute(pool)
+        .await
+        .is_ok()
+}
+
+/// Returns the session stored under `id`, unless it does not exist or has expired.
+pub async fn find_session(pool: &SqlitePool, id: &str) -> Option<Session> {
+    // Joining on users ends the sessions of deleted accounts and picks up role changes
+    sqlx::query_as::<_, Session>(
+        "SELECT s.username, u.role FROM sessions s JOIN users u ON u.username = s.username
+         WHERE s.id = ? AND s.expires_at > ?"
+    )
+        .bind(id)
+        .bind(chrono::Utc::now().format(SQLITE_TIME_FORMAT).to_string())
+        .fetch_optional(pool)
+        .await
+        .unwrap_or(None)
+}
+
+pub async fn delete_session(pool: &SqlitePool, id: &str) {
+    let _ = sqlx::query("DELETE FROM sessions WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await;
+}
+
+/// Ends every session, returning how many were removed.
+pub async fn delete_all_sessions(pool: &SqlitePool) -> u64 {
+    sqlx::query("DELETE FROM sessions")
+        .execute(pool)
+        .await
+        .map(|r| r.rows_affected())
+        .unwrap_or(0)
+}
+
+/// Ends every session of one user, e.g. after their password changes.
+pub async fn delete_user_sessions(pool: &SqlitePool, username: &str) {
+    let _ = sqlx::query("DELETE FROM sessions WHERE username = ?")
+        .bind(username)
+        .execute(pool)
+        .await;
+}
+
+pub async fn prune_sessions(pool: &SqlitePool) {
+    let _ = sqlx::query("DELETE FROM sessions WHERE expires_at <= ?")
+        .bind(chrono::Utc::now().format(SQLITE_TIME_FORMAT).to_string())
+        .execute(pool)
+        .await;
+}
+
+// ===================== API Tokens =====================
+
+/// A token as listed to its owner; the token itself is never stored.
+#[derive(serde::Serialize, sqlx::FromRow)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    /// Space-separated scope names.
+    pub scopes: String,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+/// A valid token joined with its owner, for authenticating a request.
+#[derive(sqlx::FromRow)]
+pub struct TokenOwner {
+    pub id: i64,
+    pub name: String,
+    pub scopes: String,
+    pub username: String,
+    pub role: String,
+}
+
+pub async fn create_api_token(
+    pool: &SqlitePool,
+    username: &str,
+    name: &str,
+    token_hash: &str,
+    scopes: &str,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("INSERT INTO api_tokens (username, name, token_hash, scopes, expires_at) VALUES (?, ?, ?, ?, ?) RETURNING id")
+        .bind(username)
+        .bind(name)
+        .bind(token_hash)
+        .bind(scopes)
+        .bind(expires_at.map(|at| at.format(SQLITE_TIME_FORMAT).to_string()))
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn list_api_tokens(pool: &SqlitePool, username: &str) -> Result<Vec<ApiToken>, sqlx::Error> {
+    sqlx::query_as::<_, ApiToken>(
+        "SELECT id, name, scopes, created_at, expires_at, last_used_at FROM api_tokens
+         WHERE username = ? ORDER BY created_at DESC, id DESC"
+    )
+        .bind(username)
+        .fetch_all(pool)
+        .await
+}
+
+/// Returns the token stored under `token_hash`, unless it does not exist, has expired,
+/// or its owner was deleted.
+pub async fn find_api_token(pool: &SqlitePool, token_hash: &str) -> Option<TokenOwner> {
+    sqlx::query_as::<_, TokenOwner>(
+        "SELECT t.id, t.name, t.scopes, t.username, u.role FROM api_tokens t JOIN users u ON u.username = t.username
+         WHERE t.token_hash = ? AND (t.expires_at IS NULL OR t.expires_at > ?)"
+    )
+        .bind(token_hash)
+        .bind(chrono::Utc::now().format(SQLITE_TIME_FORMAT).to_string())
+        .fetch_optional(pool)
+        .await
+        .unwrap_or(None)
+}
+
+/// Records that a token was used, at most once a minute so polling scripts do not write on every request.
+pub async fn touch_api_token(pool: &SqlitePool, id: i64) {
+    let now = chrono::Utc::now();
+    let _ = sqlx::query("UPDATE api_tokens SET last_used_at = ? WHERE id = ? AND (last_used_at IS NULL OR last_used_at < ?)")
+        .bind(now.format(SQLITE_TIME_FORMAT).to_string())
+        .bind(id)
+        .bind((now - chrono::Duration::minutes(1)).format(SQLITE_TIME_FORMAT).to_string())
+        .execute(pool)
+        .await;
+}
+
+/// Revokes one of `username`'s tokens. Returns the token's name, or `None` if they have no such token.
+pub async fn delete_api_token(pool: &SqlitePool, id: i64, username: &str) -> Option<String> {
+    sqlx::query_scalar("DELETE FROM api_tokens WHERE id = ? AND username = ? RETURNING name")
+        .bind(id)
+        .bind(username)
+        .fetch_optional(pool)
+        .await
+        .unwrap_or(None)
+}
+
+pub async fn delete_user_api_tokens(pool: &SqlitePool, username: &str) {
+    let _ = sqlx::query("DELETE FROM api_tokens WHERE username = ?")
+        .bind(username)
+        .execute(pool)
+        .await;
+}
+
+// ===================== Service Checks =====================
+
+pub async fn record_checks(pool: &SqlitePool, statuses: &[ServiceStatus]) {
+    for status in statuses {
+        let _ = sqlx::query(
+            "INSERT INTO service_checks (service, active, timed_out, latency_ms, message, version) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+            .bind(&status.id)
+            .bind(status.active)
+            .bind(status.timed_out)
+            .bind(status.latency_ms.map(|ms| ms as i64))
+            .bind(&status.message)
+            .bind(&status.version)
+            .execute(pool)
+            .await;
+    }
+}
+
+pub async fn prune_checks(pool: &SqlitePool, before: chrono::DateTime<chrono::Utc>) {
+    let _ = sqlx::query("DELETE FROM service_checks WHERE timestamp < ?")
+        .bind(before.format(SQLITE_TIME_FORMAT).to_string())
+        .execute(pool)
+        .await;
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+pub struct UptimeSummary {
+    pub service: String,
+    pub checks: i64,
+    pub up_checks: i64,
+    pub avg_latency_ms: Option<f64>,
+}
+
+pub async fn uptime_summary(pool: &SqlitePool, since: chrono::DateTime<chrono::Utc>) -> Result<Vec<UptimeSummary>, sqlx::Error> {
+    sqlx::query_as::<_, UptimeSummary>(
+        "SELECT service, COUNT(*) AS checks, SUM(active) AS up_checks, AVG(latency_ms) AS avg_latency_ms
+         FROM service_checks WHERE timestamp >= ? GROUP BY service ORDER BY service"
+    )
+        .bind(since.format(SQLITE_TIME_FORMAT).to_string())
+        .fetch_all(pool)
+        .await
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+pub struct ServiceCheck {
+    pub timestamp: String,
+    pub active: bool,
+    pub timed_out: bool,
+    pub latency_ms: Option<i64>,
+    pub message: String,
+    pub version: Option<String>,
+}
+
+pub async fn service_checks(pool: &SqlitePool, service: &str, since: chrono::DateTime<chrono::Utc>) -> Result<Vec<ServiceCheck>, sqlx::Error> {
+    sqlx::query_as::<_, ServiceCheck>(
+        "SELECT timestamp, active, timed_out, latency_ms, message, version
+         FROM service_checks WHERE service = ? AND timestamp >= ? ORDER BY timestamp, id"
+    )
+        .bind(service)
+        .bind(since.format(SQLITE_TIME_FORMAT).to_string())
+        .fetch_all(pool)
+        .await
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+pub struct CheckBucket {
+    /// Unix time (seconds) at which the bucket starts.
+    pub bucket_start: i64,
+    pub checks: i64,
+    pub up_checks: i64,
+    pub avg_latency_ms: Option<f64>,
+    pub max_latency_ms: Option<i64>,
+}
+
+/// Aggregates a service's checks into fixed-size time buckets for availability bars and latency charts.
+pub async fn check_series(pool: &SqlitePool, service: &str, since: chrono::DateTime<chrono::Utc>, bucket_secs: i64) -> Result<Vec<CheckBucket>, sqlx::Error> {
+    sqlx::query_as::<_, CheckBucket>(
+        "SELECT (CAST(strftime('%s', timestamp) AS INTEGER) / ?1) * ?1 AS bucket_start,
+                COUNT(*) AS checks, SUM(active) AS up_checks,
+                AVG(latency_ms) AS avg_latency_ms, MAX(latency_ms) AS max_latency_ms
+         FROM service_checks WHERE service = ?2 AND timestamp >= ?3
+         GROUP BY bucket_start ORDER BY bucket_start"
+    )
+        .bind(bucket_secs)
+        .bind(service)
+        .bind(since.format(SQLITE_TIME_FORMAT).to_string())
+        .fetch_all(pool)
+        .await
+}
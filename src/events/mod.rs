@@ -0,0 +1,50 @@
+use crate::api::ServiceStatus;
+use crate::db::AuditLog;
+use axum::response::sse::Event;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// How many events a subscriber may fall behind before it starts skipping ahead.
+const CHANNEL_CAPACITY: usize = 64;
+
+/// A live update pushed to dashboard pages over `/api/events`.
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum DashboardEvent {
+    /// The poller produced service statuses that differ from the previous snapshot.
+    Status {
+        as_of: Option<DateTime<Utc>>,
+        services: Vec<ServiceStatus>,
+    },
+    /// Torrents that were added or changed since the last check, and the ids that disappeared.
+    Torrents {
+        changed: Vec<serde_json::Value>,
+        removed: Vec<i64>,
+    },
+    /// A freshly written audit log entry.
+    Audit(Box<AuditLog>),
+}
+
+impl DashboardEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            DashboardEvent::Status { .. } => "status",
+            DashboardEvent::Torrents { .. } => "torrents",
+            DashboardEvent::Audit(_) => "audit",
+        }
+    }
+
+    /// Whether only admins may receive the event, as they alone may read the audit log.
+    pub fn admin_only(&self) -> bool {
+        matches!(self, DashboardEvent::Audit(_))
+    }
+
+    pub fn to_sse(&self) -> Result<Event, axum::Error> {
+        Event::default().event(self.name()).json_data(self)
+    }
+}
+
+pub fn channel() -> broadcast::Sender<DashboardEvent> {
+    broadcast::channel(CHANNEL_CAPACITY).0
+}
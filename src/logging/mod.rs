@@ -0,0 +1,121 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+pub mod rotate;
+pub mod tail;
+
+use rotate::{RotatingFile, Rotation};
+
+/// The application log, relative to the working directory.
+pub const LOG_FILE: &str = "data/app.log";
+/// The same events as JSON lines, which the system log viewer reads back.
+pub const JSON_LOG_FILE: &str = "data/app.jsonl";
+/// How many lines a live follower may fall behind before it starts skipping ahead.
+const FEED_CAPACITY: usize = 256;
+const DEFAULT_FILTER: &str = "media_dashboard=info,tower_http=info,axum=info";
+const DEFAULT_MAX_SIZE_MB: u64 = 10;
+const DEFAULT_KEEP_FILES: usize = 5;
+
+/// How the application log file rotates, from the `log_*` startup settings.
+pub struct LogSettings {
+    rotation: Rotation,
+    keep: usize,
+}
+
+impl LogSettings {
+    /// `log_rotation` is `size` (the default, at `log_max_size_mb`) or `daily`;
+    /// `log_keep_files` old files are kept.
+    pub fn from_settings<'a>(setting: impl Fn(&str) -> Option<&'a str>) -> Result<LogSettings, String> {
+        let rotation = match setting("log_rotation").map(str::trim) {
+            None | Some("size") => {
+                let max_mb = match setting("log_max_size_mb") {
+                    Some(value) => value
+                        .trim()
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|&mb| mb > 0)
+                        .ok_or_else(|| format!("log_max_size_mb: '{}' is not a positive number", value))?,
+                    None => DEFAULT_MAX_SIZE_MB,
+                };
+                Rotation::Size(max_mb.saturating_mul(1024 * 1024))
+            }
+            Some("daily") => Rotation::Daily,
+            Some(other) => return Err(format!("log_rotation: '{}' is neither 'size' nor 'daily'", other)),
+        };
+        let keep = match setting("log_keep_files") {
+            Some(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| format!("log_keep_files: '{}' is not a number", value))?,
+            None => DEFAULT_KEEP_FILES,
+        };
+        Ok(LogSettings { rotation, keep })
+    }
+}
+
+/// JSON log lines as they are written, for live followers of the system log.
+pub type LogFeed = broadcast::Sender<Arc<str>>;
+
+/// Logs to the console and to `LOG_FILE`, and as JSON to `JSON_LOG_FILE` and the returned
+/// feed, filtered by `RUST_LOG`. If the files cannot be opened (e.g. Docker volume
+/// permission issues), logging falls back to the console and the feed only.
+pub fn init(settings: &LogSettings) -> LogFeed {
+    let filter = tracing_subscriber::EnvFilter::new(std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_FILTER.into()));
+    // Ensure the data directory exists before attempting to create the log file
+    let _ = std::fs::create_dir_all("data");
+    let open = |path: &str| RotatingFile::open(Path::new(path), settings.rotation, settings.keep);
+    let (log_file, json_file, failure) = match (open(LOG_FILE), open(JSON_LOG_FILE)) {
+        (Ok(log_file), Ok(json_file)) => (Some(log_file), Some(json_file), None),
+        (Err(e), _) | (_, Err(e)) => (None, None, Some(e)),
+    };
+    let feed = broadcast::channel(FEED_CAPACITY).0;
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(log_file.map(|file| tracing_subscriber::fmt::layer().with_ansi(false).with_writer(file)))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(JsonLog { file: json_file, feed: feed.clone() }),
+        )
+        .init();
+    if let Some(e) = failure {
+        tracing::warn!("Failed to open {}: {}. Continuing with console-only logging. Ensure the volume permissions allow writing by the 'dashboard' user.", LOG_FILE, e);
+    }
+    feed
+}
+
+/// Writes each JSON line to the log file, if open, and to the live feed.
+struct JsonLog {
+    file: Option<RotatingFile>,
+    feed: LogFeed,
+}
+
+impl Write for &JsonLog {
+    /// The formatter writes each event as one complete line.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(mut file) = self.file.as_ref() {
+            file.write_all(buf)?;
+        }
+        if self.feed.receiver_count() > 0 {
+            let _ = self.feed.send(String::from_utf8_lossy(buf).trim_end().into());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_ref().map_or(Ok(()), |mut file| file.flush())
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for JsonLog {
+    type Writer = &'a JsonLog;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
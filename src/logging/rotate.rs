@@ -0,0 +1,112 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// When the application log moves aside for a fresh file.
+#[derive(Clone, Copy)]
+pub enum Rotation {
+    /// Before a write would take the file past this many bytes.
+    Size(u64),
+    /// On the first write of a new day (UTC).
+    Daily,
+}
+
+/// A log file that rotates itself: `app.log` becomes `app.log.1`, the previous `app.log.1`
+/// becomes `app.log.2`, and so on, keeping `keep` old files.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    keep: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// `None` after reopening failed; lines are dropped until the next rotation.
+    file: Option<File>,
+    size: u64,
+    day: NaiveDate,
+}
+
+impl RotatingFile {
+    /// Appends to `path`, after rotating it right away if it is already due, e.g. because
+    /// it was last written on an earlier day.
+    pub fn open(path: &Path, rotation: Rotation, keep: usize) -> io::Result<RotatingFile> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        let today = Utc::now().date_naive();
+        let day = metadata.modified().map(|at| DateTime::<Utc>::from(at).date_naive()).unwrap_or(today);
+        let log = RotatingFile {
+            path: path.to_path_buf(),
+            rotation,
+            keep,
+            state: Mutex::new(State { file: Some(file), size: metadata.len(), day }),
+        };
+        {
+            let mut state = log.state.lock().unwrap_or_else(|e| e.into_inner());
+            if log.is_due(&state, 0, today) {
+                log.rotate(&mut state, today);
+            }
+        }
+        Ok(log)
+    }
+
+    fn is_due(&self, state: &State, incoming: usize, today: NaiveDate) -> bool {
+        match self.rotation {
+            Rotation::Size(max) => state.size > 0 && state.size + incoming as u64 > max,
+            Rotation::Daily => state.day != today,
+        }
+    }
+
+    fn rotate(&self, state: &mut State, today: NaiveDate) {
+        state.file = None;
+        if self.keep == 0 {
+            let _ = std::fs::remove_file(&self.path);
+        } else {
+            let _ = std::fs::remove_file(rotated_path(&self.path, self.keep));
+            for n in (1..self.keep).rev() {
+                let _ = std::fs::rename(rotated_path(&self.path, n), rotated_path(&self.path, n + 1));
+            }
+            let _ = std::fs::rename(&self.path, rotated_path(&self.path, 1));
+        }
+        state.file = OpenOptions::new().create(true).append(true).open(&self.path).ok();
+        state.size = 0;
+        state.day = today;
+    }
+}
+
+/// The `n`th most recent rotated file of the log at `path`, e.g. `app.log.1`.
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let today = Utc::now().date_naive();
+        if self.is_due(&state, buf.len(), today) {
+            self.rotate(&mut state, today);
+        }
+        if let Some(file) = state.file.as_mut() {
+            file.write_all(buf)?;
+            state.size += buf.len() as u64;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.file.as_mut().map_or(Ok(()), |file| file.flush())
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for RotatingFile {
+    type Writer = &'a RotatingFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
@@ -0,0 +1,225 @@
+use super::rotate::rotated_path;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+use tracing::Level;
+
+/// Entries per page when the caller does not ask for a size, and the most it may ask for.
+pub const DEFAULT_PAGE_SIZE: usize = 200;
+pub const MAX_PAGE_SIZE: usize = 1000;
+/// Bytes read per step while a file is scanned backwards.
+const BLOCK_SIZE: u64 = 64 * 1024;
+/// How much of a file's first line identifies it.
+const FINGERPRINT_SIZE: u64 = 4096;
+
+/// Which JSON log entries to show. Shared by the paged reads and the live feed.
+#[derive(Clone, Default)]
+pub struct LogFilter {
+    /// The least severe level shown, e.g. `WARN` for warnings and errors.
+    level: Option<Level>,
+    /// A module path prefix, e.g. `media_dashboard::poller`.
+    target: Option<String>,
+    /// Lowercase text to find in the message or other fields.
+    search: Option<String>,
+}
+
+impl LogFilter {
+    pub fn new(level: Option<&str>, target: Option<&str>, search: Option<&str>) -> Result<LogFilter, String> {
+        let level = level
+            .map(|level| Level::from_str(level.trim()).map_err(|_| format!("level: '{}' is not a log level", level)))
+            .transpose()?;
+        Ok(LogFilter {
+            level,
+            target: target.map(|target| target.trim().to_string()),
+            search: search.map(str::to_lowercase),
+        })
+    }
+
+    /// The entry written as `line`, if it is one that matches.
+    pub fn matches(&self, line: &str) -> Option<Value> {
+        let entry: Value = serde_json::from_str(line).ok()?;
+        if let Some(min) = self.level {
+            // More verbose levels compare greater
+            let level = Level::from_str(entry["level"].as_str()?).ok()?;
+            if level > min {
+                return None;
+            }
+        }
+        if let Some(target) = &self.target {
+            if !entry["target"].as_str()?.starts_with(target.as_str()) {
+                return None;
+            }
+        }
+        if let Some(search) = &self.search {
+            if !entry["fields"].to_string().to_lowercase().contains(search.as_str()) {
+                return None;
+            }
+        }
+        Some(entry)
+    }
+}
+
+/// Where the next older page starts: the `file`th rotated log (0 for the current one), read
+/// backwards from byte `offset`. Rotation renumbers the files, so the cursor also carries a
+/// digest of that file's first line to find it again. Written as `file:offset:first_line`.
+#[derive(Clone, Copy)]
+pub struct Cursor {
+    file: usize,
+    offset: u64,
+    first_line: u64,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{:016x}", self.file, self.offset, self.first_line)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Cursor, String> {
+        let invalid = || format!("cursor: '{}' is not a cursor from a previous page", value);
+        let mut parts = value.split(':');
+        let (Some(file), Some(offset), Some(first_line), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        Ok(Cursor {
+            file: file.parse().map_err(|_| invalid())?,
+            offset: offset.parse().map_err(|_| invalid())?,
+            first_line: u64::from_str_radix(first_line, 16).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Why a page could not be read.
+pub enum PageError {
+    /// The file the cursor points into has rotated away; paging has to start over.
+    Expired,
+    Io(io::Error),
+}
+
+impl From<io::Error> for PageError {
+    fn from(e: io::Error) -> PageError {
+        PageError::Io(e)
+    }
+}
+
+/// Up to `limit` entries of the log at `path` matching `filter`, newest first, continuing
+/// into the rotated files. Starts at the end, or where `cursor` says a previous page stopped,
+/// and returns the cursor of the next page unless the oldest file has been read.
+pub fn page(path: &Path, filter: &LogFilter, cursor: Option<Cursor>, limit: usize) -> Result<(Vec<Value>, Option<Cursor>), PageError> {
+    let (mut file, mut log, mut offset) = match cursor {
+        Some(cursor) => {
+            let (file, log) = locate(path, &cursor)?;
+            (file, log, Some(cursor.offset))
+        }
+        None => match open(path, 0)? {
+            Some(log) => (0, log, None),
+            None => return Ok((Vec::new(), None)),
+        },
+    };
+    let mut entries = Vec::new();
+    loop {
+        let len = log.metadata()?.len();
+        let end = match offset.take() {
+            // Logs only grow, so this is not the file the cursor was handed out for
+            Some(offset) if offset > len => return Err(PageError::Expired),
+            Some(offset) => offset,
+            None => len,
+        };
+        let mut lines = ReverseLines { file: log, pos: end, buf: Vec::new() };
+        while let Some((start, line)) = lines.next_line()? {
+            if let Some(entry) = filter.matches(&line) {
+                entries.push(entry);
+                if entries.len() >= limit {
+                    let first_line = first_line_digest(&mut lines.file)?;
+                    return Ok((entries, Some(Cursor { file, offset: start, first_line })));
+                }
+            }
+        }
+        file += 1;
+        log = match open(path, file)? {
+            Some(log) => log,
+            None => return Ok((entries, None)),
+        };
+    }
+}
+
+/// The `n`th rotated log, 0 being the current one, if it exists.
+fn open(path: &Path, n: usize) -> io::Result<Option<File>> {
+    let current = if n == 0 { path.to_path_buf() } else { rotated_path(path, n) };
+    match File::open(current) {
+        Ok(log) => Ok(Some(log)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// The file `cursor` was handed out for and its current number. Rotations since then only
+/// move it to higher numbers, until it is deleted.
+fn locate(path: &Path, cursor: &Cursor) -> Result<(usize, File), PageError> {
+    for n in cursor.file.. {
+        let Some(mut log) = open(path, n)? else {
+            return Err(PageError::Expired);
+        };
+        if first_line_digest(&mut log)? == cursor.first_line {
+            return Ok((n, log));
+        }
+    }
+    Err(PageError::Expired)
+}
+
+fn first_line_digest(file: &mut File) -> io::Result<u64> {
+    let mut start = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.take(FINGERPRINT_SIZE).read_to_end(&mut start)?;
+    let line = start.split(|&b| b == b'\n').next().unwrap_or_default();
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&Sha256::digest(line)[..8]);
+    Ok(u64::from_be_bytes(prefix))
+}
+
+/// The lines of a file read backwards from a byte offset, a block at a time.
+struct ReverseLines {
+    file: File,
+    /// Where the bytes in `buf` start.
+    pos: u64,
+    /// Read bytes not yet returned as lines.
+    buf: Vec<u8>,
+}
+
+impl ReverseLines {
+    /// The previous non-empty line and the offset it starts at.
+    fn next_line(&mut self) -> io::Result<Option<(u64, String)>> {
+        loop {
+            if let Some(newline) = self.buf.iter().rposition(|&b| b == b'\n') {
+                let line = self.buf.split_off(newline + 1);
+                self.buf.truncate(newline);
+                if !line.is_empty() {
+                    let start = self.pos + newline as u64 + 1;
+                    return Ok(Some((start, String::from_utf8_lossy(&line).into_owned())));
+                }
+                continue;
+            }
+            if self.pos == 0 {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                let line = std::mem::take(&mut self.buf);
+                return Ok(Some((0, String::from_utf8_lossy(&line).into_owned())));
+            }
+            let block = BLOCK_SIZE.min(self.pos);
+            self.pos -= block;
+            let mut chunk = vec![0; block as usize];
+            self.file.seek(SeekFrom::Start(self.pos))?;
+            self.file.read_exact(&mut chunk)?;
+            chunk.append(&mut self.buf);
+            self.buf = chunk;
+        }
+    }
+}
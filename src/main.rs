@@ -1,68 +1,73 @@
 use axum::{
     routing::{get, post, delete},
     Json, Router,
-    extract::{State, Path, Query, Request},
+    extract::{State, Path, Query, Request, ConnectInfo},
     middleware::Next,
-    response::{Response, IntoResponse},
+    response::{Response, IntoResponse, sse::{Event, KeepAlive, Sse}},
     http::{header, StatusCode},
 };
 use std::net::SocketAddr;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use tower_http::services::ServeDir;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use reqwest::Client;
 
 mod api;
+mod audit;
+mod auth;
 mod db;
+mod events;
+mod logging;
+mod overrides;
+mod poller;
+mod secrets;
+mod server;
+mod transfer;
 use api::ServiceStatus;
 use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
 
 #[derive(Serialize, Deserialize, Clone, Default)]
 struct Config {
+    /// Seconds between background refreshes of status, calendar and disk space.
+    #[serde(default = "default_poll_interval")]
+    poll_interval: u64,
+    /// How long a browser login stays valid, in hours.
+    #[serde(default = "default_session_lifetime")]
+    session_lifetime_hours: u64,
+    /// Whether `Authorization: Basic` is still accepted, for scripts that cannot log in.
     #[serde(default)]
-    dashboard_user: String,
-    #[serde(default)]
-    dashboard_pass: String,
-    #[serde(default)]
-    sonarr_url: String,
-    #[serde(default)]
-    sonarr_key: String,
-    #[serde(default)]
-    radarr_url: String,
-    #[serde(default)]
-    radarr_key: String,
-    #[serde(default)]
-    jackett_url: String,
-    #[serde(default)]
-    jackett_key: String,
-    #[serde(default)]
-    transmission_url: String,
-    #[serde(default)]
-    transmission_user: String,
-    #[serde(default)]
-    transmission_pass: String,
-    #[serde(default)]
-    plex_url: String,
-    #[serde(default)]
-    plex_token: String,
+    basic_auth_enabled: bool,
+    /// Single sign-on through an OpenID Connect provider, off until an issuer is set.
     #[serde(default)]
-    pub jellyfin_url: String,
+    oidc: auth::oidc::OidcSettings,
+    /// How long audit entries and login events are kept.
     #[serde(default)]
-    pub jellyfin_key: String,
+    retention: audit::retention::RetentionSettings,
+    /// Connection settings of every registered service, flattened so the JSON
+    /// keeps its `sonarr_url`/`sonarr_key` shape.
+    #[serde(flatten)]
+    services: api::ServiceSettings,
+    /// Additional instances of services that support more than one, e.g. a 4K Radarr.
     #[serde(default)]
-    pub emby_url: String,
-    #[serde(default)]
-    pub emby_key: String,
-    #[serde(default)]
-    pub prowlarr_url: String,
-    #[serde(default)]
-    pub prowlarr_key: String,
-    #[serde(default)]
-    pub lidarr_url: String,
-    #[serde(default)]
-    pub lidarr_key: String,
+    instances: Vec<api::ServiceInstance>,
+}
+
+impl Config {
+    /// The primary instance of every service followed by the additional ones.
+    fn all_instances(&self) -> Vec<api::ServiceInstance> {
+        api::instances(&self.services, &self.instances)
+    }
+}
+
+fn default_poll_interval() -> u64 {
+    poller::DEFAULT_POLL_INTERVAL_SECS
+}
+
+fn default_session_lifetime() -> u64 {
+    auth::DEFAULT_SESSION_LIFETIME_HOURS
 }
 
 #[derive(Clone)]
@@ -70,6 +75,40 @@ struct AppState {
     config: Arc<tokio::sync::RwLock<Config>>,
     client: Client,
     db: SqlitePool,
+    snapshot: Arc<tokio::sync::RwLock<poller::Snapshot>>,
+    /// Wakes the background poller early, e.g. after the connection settings change.
+    refresh: Arc<tokio::sync::Notify>,
+    events: tokio::sync::broadcast::Sender<events::DashboardEvent>,
+    /// Encrypts service secrets before they are written to `dashboard_settings`.
+    secrets: Arc<secrets::SecretKey>,
+    /// Settings pinned by `MD_*` environment variables or the `--config` file.
+    overrides: Arc<overrides::Overrides>,
+    /// Forward authentication by a trusted proxy, when configured.
+    proxy_auth: Option<Arc<auth::proxy::ProxyAuth>>,
+    /// Proxies whose `X-Forwarded-*` headers are believed.
+    trusted_proxies: Arc<auth::proxy::TrustedProxies>,
+    /// Path prefix the dashboard is served under, e.g. "/dashboard", or empty at the root.
+    base_path: String,
+    /// Whether the listener serves HTTPS, for absolute URLs such as the OIDC callback.
+    tls: bool,
+    /// OIDC logins in flight and the provider's cached keys.
+    oidc: Arc<auth::oidc::Oidc>,
+    lockout: Arc<tokio::sync::Mutex<auth::lockout::LoginThrottle>>,
+    /// Basic credentials that passed verification, keyed by the digest of their
+    /// `Authorization` header, so argon2 runs once per credential rather than on every request.
+    verified_auth: Arc<tokio::sync::RwLock<HashMap<[u8; 32], VerifiedAuth>>>,
+    /// JSON log lines as they are written, for `/api/logs/system/follow`.
+    log_feed: logging::LogFeed,
+}
+
+/// Upper bound on remembered Basic credentials; the cache is simply cleared when it fills up.
+const MAX_VERIFIED_AUTH: usize = 64;
+
+/// The user and stored hash an accepted `Authorization` header was checked against,
+/// so a password change invalidates it.
+struct VerifiedAuth {
+    username: String,
+    stored_pass: String,
 }
 
 #[derive(Deserialize)]
@@ -77,6 +116,12 @@ struct SearchQuery {
     term: Option<String>,
 }
 
+/// Selects which instance of a service a request targets; the primary one when absent.
+#[derive(Deserialize)]
+struct InstanceQuery {
+    instance: Option<String>,
+}
+
 #[derive(Deserialize)]
 struct DeleteQuery {
     #[serde(rename = "deleteFiles")]
@@ -85,6 +130,49 @@ struct DeleteQuery {
     delete_data: Option<bool>,
 }
 
+#[derive(Deserialize)]
+struct LoginPayload {
+    username: String,
+    password: String,
+}
+
+/// The setup wizard creates the first admin account alongside the initial connection settings.
+#[derive(Deserialize)]
+struct SetupPayload {
+    #[serde(default)]
+    dashboard_user: String,
+    #[serde(default)]
+    dashboard_pass: String,
+    #[serde(flatten)]
+    config: Config,
+}
+
+#[derive(Deserialize)]
+struct NewUserPayload {
+    username: String,
+    password: String,
+    role: auth::Role,
+}
+
+#[derive(Deserialize)]
+struct UserUpdatePayload {
+    role: Option<auth::Role>,
+    password: Option<String>,
+    /// `true` links the account to the next single sign-on under its name, `false` unlinks it.
+    sso_link: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct PasswordChangePayload {
+    current_password: String,
+    new_password: String,
+}
+
+#[derive(Deserialize)]
+struct UptimeQuery {
+    window: Option<String>,
+}
+
 #[derive(Deserialize)]
 struct TorrentAddPayload {
     filename: String,
@@ -96,44 +184,64 @@ fn internal_err(e: impl std::fmt::Display) -> AppError {
     (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
 }
 
-fn encode_basic_auth(user: &str, pass: &str) -> String {
-    let b64_chars: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/".chars().collect();
-    let input = format!("{}:{}", user, pass);
-    let bytes = input.as_bytes();
-    let mut output = String::new();
-    let mut i = 0;
-    while i < bytes.len() {
-        let b0 = bytes[i];
-        let b1 = if i + 1 < bytes.len() { bytes[i + 1] } else { 0 };
-        let b2 = if i + 2 < bytes.len() { bytes[i + 2] } else { 0 };
-        
-        let out0 = b0 >> 2;
-        let out1 = ((b0 & 0x03) << 4) | (b1 >> 4);
-        let out2 = ((b1 & 0x0F) << 2) | (b2 >> 6);
-        let out3 = b2 & 0x3F;
-        
-        output.push(b64_chars[out0 as usize]);
-        output.push(b64_chars[out1 as usize]);
-        if i + 1 < bytes.len() { output.push(b64_chars[out2 as usize]); } else { output.push('='); }
-        if i + 2 < bytes.len() { output.push(b64_chars[out3 as usize]); } else { output.push('='); }
-        i += 3;
+/// Resolves the instance of `kind` named by `?instance=`, or its primary instance.
+async fn service_instance(state: &AppState, kind: &str, id: Option<&str>) -> Result<api::ServiceInstance, AppError> {
+    let id = id.unwrap_or(kind);
+    state.config.read().await
+        .all_instances()
+        .into_iter()
+        .find(|instance| instance.id == id && instance.kind == kind)
+        .ok_or_else(|| (axum::http::StatusCode::NOT_FOUND, format!("No {} instance '{}'", kind, id)))
+}
+
+/// Writes an audit log entry and pushes it to live `/api/events` subscribers.
+/// `user` is the dashboard user behind the action, or `None` for system events.
+async fn audit(state: &AppState, user: Option<&auth::Identity>, service: &str, action: &str, details: &str) {
+    audit_event(state, user, db::AuditEvent::new(service, action, details)).await;
+}
+
+/// Like `audit`, for an entry that names its target or records a change of state.
+async fn audit_event(state: &AppState, user: Option<&auth::Identity>, event: db::AuditEvent) {
+    let username = user.map(|u| u.username.as_str());
+    let token = user.and_then(|u| u.token.as_ref()).map(|t| t.name.as_str());
+    if let Some(entry) = db::record_event(&state.db, username, token, &event).await {
+        let _ = state.events.send(events::DashboardEvent::Audit(Box::new(entry)));
+    }
+}
+
+/// Audits the outcome of a call to a service, so failed attempts are recorded as well, and
+/// passes its result on. `attempt` describes the action for the failure entry, e.g.
+/// "Failed to delete series 'Severance'".
+async fn audit_result<T, E: std::fmt::Display>(
+    state: &AppState,
+    user: &auth::Identity,
+    event: db::AuditEvent,
+    attempt: &str,
+    result: Result<T, E>,
+) -> Result<T, AppError> {
+    match result {
+        Ok(value) => {
+            audit_event(state, Some(user), event).await;
+            Ok(value)
+        }
+        Err(e) => {
+            audit_event(state, Some(user), event.failed(format!("{}: {}", attempt, e))).await;
+            Err(internal_err(e))
+        }
     }
-    format!("Basic {}", output)
 }
 
 async fn auth_middleware(
     State(state): State<Arc<AppState>>,
-    req: Request,
+    mut req: Request,
     next: Next,
 ) -> Response {
-    let (expected_user, expected_pass) = {
-        let config = state.config.read().await;
-        (config.dashboard_user.clone(), config.dashboard_pass.clone())
-    };
+    let needs_setup = db::count_users(&state.db).await == 0;
+    let basic_auth_enabled = state.config.read().await.basic_auth_enabled;
     
     let path = req.uri().path().to_string();
-    let needs_setup = expected_user.is_empty() || expected_pass.is_empty();
-    let is_setup_asset = path == "/setup.html" || path.starts_with("/style.css") || path.starts_with("/fonts/") || path == "/favicon.svg" || path == "/api/setup" || path == "/api/needs-setup";
+    let is_public_asset = path.starts_with("/style.css") || path.starts_with("/fonts/") || path == "/favicon.svg";
+    let is_setup_asset = is_public_asset || path == "/setup.html" || path == "/api/setup" || path == "/api/needs-setup";
 
     if needs_setup {
         if is_setup_asset {
@@ -141,31 +249,292 @@ async fn auth_middleware(
         } else if path.starts_with("/api/") {
             return (StatusCode::FORBIDDEN, "Setup Required").into_response();
         } else {
-            let headers = [(header::LOCATION, "/setup.html")];
+            let headers = [(header::LOCATION, format!("{}/setup.html", state.base_path))];
             return (StatusCode::TEMPORARY_REDIRECT, headers, "").into_response();
         }
     }
     
-    if path == "/api/needs-setup" || path == "/api/setup" {
+    let is_login = path == "/api/login" || path == "/api/login/options" || path.starts_with("/api/oidc/") || path == "/login.html";
+    if is_public_asset || path == "/api/needs-setup" || path == "/api/setup" || is_login {
         return next.run(req).await;
     }
     
-    let expected_auth = encode_basic_auth(&expected_user, &expected_pass);
-    
-    if let Some(auth_header) = req.headers().get(header::AUTHORIZATION) {
-        if let Ok(auth_str) = auth_header.to_str() {
-            if auth_str == expected_auth {
-                if path == "/setup.html" {
-                    let headers = [(header::LOCATION, "/")];
-                    return (StatusCode::TEMPORARY_REDIRECT, headers, "").into_response();
-                }
-                return next.run(req).await;
+    // A user vouched for by the trusted proxy wins over whatever session the browser still holds
+    let proxy_user = state.proxy_auth.as_ref()
+        .and_then(|proxy| Some((proxy, proxy.user(peer_addr(&req).ip(), req.headers())?)));
+    let authorization = req.headers().get(header::AUTHORIZATION).and_then(|h| h.to_str().ok());
+    let bearer = authorization.and_then(auth::tokens::read_bearer);
+    let mut identity = match (proxy_user, bearer, auth::read_cookie(req.headers(), auth::SESSION_COOKIE)) {
+        (Some((proxy, username)), _, _) => match proxy_identity(&state, proxy, username, req.headers()).await {
+            Some(user) => Some(user),
+            None => return (StatusCode::FORBIDDEN, format!("'{}' has no dashboard role", username)).into_response(),
+        },
+        // A script that presents a token means it, so a bad one is refused rather than ignored
+        (None, Some(token), _) => match token_identity(&state, token).await {
+            Some(user) => Some(user),
+            None => return (StatusCode::UNAUTHORIZED, "Invalid or expired API token").into_response(),
+        },
+        (None, None, Some(token)) => db::find_session(&state.db, &auth::session_id(token)).await
+            .map(|session| auth::Identity {
+                username: session.username,
+                role: auth::Role::parse(&session.role),
+                method: "session",
+                token: None,
+            }),
+        (None, None, None) => None,
+    };
+    let basic_header = authorization.filter(|_| bearer.is_none());
+    if identity.is_none() && basic_auth_enabled {
+        if let Some(auth_str) = basic_header {
+            match check_basic_auth(&state, client_ip(&state, peer_addr(&req), req.headers()), auth_str).await {
+                Ok(user) => identity = Some(user),
+                Err(AuthFailure::Invalid) => {}
+                Err(locked) => return locked.into_response(),
             }
         }
     }
+    if let Some(identity) = identity {
+        if path == "/setup.html" {
+            let headers = [(header::LOCATION, format!("{}/", state.base_path))];
+            return (StatusCode::TEMPORARY_REDIRECT, headers, "").into_response();
+        }
+        req.extensions_mut().insert(identity);
+        return next.run(req).await;
+    }
     
-    let headers = [(header::WWW_AUTHENTICATE, "Basic realm=\"media-dashboard\"")];
-    (StatusCode::UNAUTHORIZED, headers, "Unauthorized").into_response()
+    if !path.starts_with("/api/") {
+        let location = format!("{}/login.html?next={}", state.base_path, urlencoding::encode(&format!("{}{}", state.base_path, path)));
+        return (StatusCode::TEMPORARY_REDIRECT, [(header::LOCATION, location)], "").into_response();
+    }
+    // Only challenge clients that are already trying Basic, so browsers never get the native
+    // prompt; behind an authenticating proxy, that proxy does the prompting
+    if basic_auth_enabled && basic_header.is_some() && state.proxy_auth.is_none() {
+        let headers = [(header::WWW_AUTHENTICATE, "Basic realm=\"media-dashboard\"")];
+        return (StatusCode::UNAUTHORIZED, headers, "Unauthorized").into_response();
+    }
+    (StatusCode::UNAUTHORIZED, "Unauthorized").into_response()
+}
+
+/// Rejects requests whose user's role is below the required one, and API tokens without
+/// the scope covering the route. Runs after `auth_middleware`, which attaches the
+/// `Identity` this checks.
+async fn require_role(
+    State(access): State<auth::Access>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(identity) = req.extensions().get::<auth::Identity>() else {
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    };
+    if identity.role < access.role {
+        return (StatusCode::FORBIDDEN, format!("Requires the {} role", access.role.as_str())).into_response();
+    }
+    match (&identity.token, access.scope) {
+        (Some(token), Some(scope)) if !token.scopes.contains(&scope) => {
+            (StatusCode::FORBIDDEN, format!("This API token lacks the {} scope", scope.as_str())).into_response()
+        }
+        (Some(_), None) => (StatusCode::FORBIDDEN, "API tokens cannot be used here").into_response(),
+        _ => next.run(req).await,
+    }
+}
+
+/// Why a login attempt was refused.
+enum AuthFailure {
+    Invalid,
+    /// Too many recent failures from this client or for this username.
+    Locked(std::time::Duration),
+}
+
+impl IntoResponse for AuthFailure {
+    fn into_response(self) -> Response {
+        match self {
+            AuthFailure::Invalid => (StatusCode::UNAUTHORIZED, "Invalid username or password").into_response(),
+            AuthFailure::Locked(left) => {
+                let secs = left.as_secs().max(1);
+                let headers = [(header::RETRY_AFTER, secs.to_string())];
+                let message = format!("Too many failed attempts. Try again in {}s", secs);
+                (StatusCode::TOO_MANY_REQUESTS, headers, message).into_response()
+            }
+        }
+    }
+}
+
+/// Address of the connecting peer, or the unspecified address if it is unknown.
+fn peer_addr(req: &Request) -> SocketAddr {
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0)
+        .unwrap_or((std::net::Ipv4Addr::UNSPECIFIED, 0).into())
+}
+
+/// The client logins are limited and recorded by: the peer, or the address a trusted proxy
+/// forwarded the request for. `None` on the Unix socket unless a trusted proxy said who it
+/// was, since every client there shares the same loopback address.
+fn client_ip(state: &AppState, peer: SocketAddr, headers: &axum::http::HeaderMap) -> Option<std::net::IpAddr> {
+    let client = state.trusted_proxies.client(peer.ip(), headers);
+    Some(client).filter(|client| peer != server::UNIX_PEER || *client != peer.ip())
+}
+
+/// How a client appears in `login_events` and the log.
+fn client_label(ip: Option<std::net::IpAddr>) -> String {
+    ip.map_or_else(|| "unix socket".to_string(), |ip| ip.to_string())
+}
+
+/// Verifies Basic credentials against the `users` table.
+async fn check_basic_auth(state: &AppState, ip: Option<std::net::IpAddr>, auth_str: &str) -> Result<auth::Identity, AuthFailure> {
+    let fingerprint = auth::digest(auth_str);
+    let known = state.verified_auth.read().await
+        .get(&fingerprint)
+        .map(|known| (known.username.clone(), known.stored_pass.clone()));
+    if let Some((username, stored_pass)) = known {
+        if let Some(user) = db::find_user(&state.db, &username).await.filter(|u| u.password_hash == stored_pass) {
+            return Ok(basic_identity(user));
+        }
+    }
+
+    let (username, pass) = auth::parse_basic_auth(auth_str).ok_or(AuthFailure::Invalid)?;
+    let user = authenticate(state, ip, &username, pass).await?;
+    let mut verified = state.verified_auth.write().await;
+    if verified.len() >= MAX_VERIFIED_AUTH {
+        verified.clear();
+    }
+    verified.insert(fingerprint, VerifiedAuth {
+        username: user.username.clone(),
+        stored_pass: user.password_hash.clone(),
+    });
+    Ok(basic_identity(user))
+}
+
+/// The identity of a user signed in by the authenticating proxy. A dashboard user of the
+/// same name keeps their own role; anyone else gets the role their groups map to.
+async fn proxy_identity(state: &AppState, proxy: &auth::proxy::ProxyAuth, username: &str, headers: &axum::http::HeaderMap) -> Option<auth::Identity> {
+    let role = match db::find_user(&state.db, username).await {
+        Some(user) => auth::Role::parse(&user.role),
+        None => proxy.role_from_groups(headers)?,
+    };
+    Some(auth::Identity { username: username.to_string(), role, method: "proxy", token: None })
+}
+
+fn basic_identity(user: db::User) -> auth::Identity {
+    auth::Identity {
+        role: auth::Role::parse(&user.role),
+        username: user.username,
+        method: "basic",
+        token: None,
+    }
+}
+
+/// The identity behind an API token: its owner, limited to the token's scopes.
+async fn token_identity(state: &AppState, token: &str) -> Option<auth::Identity> {
+    let owner = db::find_api_token(&state.db, &auth::tokens::token_hash(token)).await?;
+    db::touch_api_token(&state.db, owner.id).await;
+    Some(auth::Identity {
+        username: owner.username,
+        role: auth::Role::parse(&owner.role),
+        method: "token",
+        token: Some(auth::tokens::TokenGrant {
+            id: owner.id,
+            name: owner.name,
+            scopes: auth::tokens::parse_scopes(&owner.scopes),
+        }),
+    })
+}
+
+/// Runs a login attempt through the lockout and records its outcome in `login_events`.
+async fn authenticate(state: &AppState, ip: Option<std::net::IpAddr>, username: &str, pass: String) -> Result<db::User, AuthFailure> {
+    let now = std::time::Instant::now();
+    let locked_for = state.lockout.lock().await.locked_for(ip, username, now);
+    if let Some(left) = locked_for {
+        db::log_login(&state.db, username, &client_label(ip), false).await;
+        return Err(AuthFailure::Locked(left));
+    }
+
+    let result = verify_credentials(state, username, pass).await;
+    {
+        let mut lockout = state.lockout.lock().await;
+        match result {
+            Some(_) => lockout.record_success(ip, username),
+            None => lockout.record_failure(ip, username, now),
+        }
+    }
+    db::log_login(&state.db, username, &client_label(ip), result.is_some()).await;
+    if result.is_none() {
+        tracing::warn!("Failed login for '{}' from {}", username, client_label(ip));
+    }
+    result.ok_or(AuthFailure::Invalid)
+}
+
+/// Checks a username and password against the `users` table, upgrading a legacy
+/// plaintext password on success. Returns the user as stored afterwards.
+async fn verify_credentials(state: &AppState, username: &str, pass: String) -> Option<db::User> {
+    let user = db::find_user(&state.db, username).await;
+    let stored = user
+        .as_ref()
+        .map(|u| u.password_hash.clone())
+        .unwrap_or_else(|| auth::dummy_hash().to_string());
+    let candidate = pass.clone();
+    let valid = tokio::task::spawn_blocking(move || auth::verify_password(&candidate, &stored))
+        .await
+        .unwrap_or(false);
+    let mut user = user.filter(|_| valid)?;
+
+    if !auth::is_hashed(&user.password_hash) {
+        user.password_hash = upgrade_plaintext_password(state, &user, pass).await;
+    }
+    Some(user)
+}
+
+/// Replaces a password stored before hashing was introduced with its argon2 hash,
+/// returning whatever is stored afterwards.
+async fn upgrade_plaintext_password(state: &AppState, user: &db::User, pass: String) -> String {
+    let hashed = match hash_password(pass).await {
+        Ok(hashed) => hashed,
+        Err((_, e)) => {
+            tracing::error!("Failed to hash legacy password of '{}': {}", user.username, e);
+            return user.password_hash.clone();
+        }
+    };
+    // Another request may have changed or upgraded the password in the meantime
+    if !db::set_user_password(&state.db, &user.username, &hashed, Some(&user.password_hash)).await {
+        return db::find_user(&state.db, &user.username).await
+            .map(|u| u.password_hash)
+            .unwrap_or_default();
+    }
+    tracing::info!("Migrated plaintext password of '{}' to argon2 hash", user.username);
+    hashed
+}
+
+async fn hash_password(pass: String) -> Result<String, AppError> {
+    tokio::task::spawn_blocking(move || auth::hash_password(&pass))
+        .await
+        .map_err(internal_err)?
+        .map_err(internal_err)
+}
+
+/// Command-line arguments: an optional subcommand such as `rotate-key`, and `--config <file>`.
+struct Cli {
+    command: Option<String>,
+    config: Option<std::path::PathBuf>,
+}
+
+fn parse_args() -> Result<Cli, String> {
+    let mut cli = Cli { command: None, config: None };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            cli.config = Some(args.next().ok_or("--config needs a file path")?.into());
+        } else if let Some(path) = arg.strip_prefix("--config=") {
+            cli.config = Some(path.into());
+        } else if arg.starts_with('-') || cli.command.is_some() {
+            return Err(format!("Unexpected argument '{}'", arg));
+        } else {
+            cli.command = Some(arg);
+        }
+    }
+    if cli.config.is_none() {
+        cli.config = std::env::var(overrides::CONFIG_ENV).ok().filter(|v| !v.is_empty()).map(Into::into);
+    }
+    Ok(cli)
 }
 
 #[tokio::main]
@@ -174,104 +543,251 @@ async fn main() {
         eprintln!("PANIC occurred: {:?}", info);
     }));
 
+    // A `.env` next to the binary may set anything below, from RUST_LOG to MD_SONARR_URL
+    let _ = dotenvy::dotenv();
+    let cli = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
     eprintln!("STAGE 0: Starting Media Dashboard...");
-    
-    // Ensure the data directory exists before attempting to create the log file
-    let _ = std::fs::create_dir_all("data");
-
-    // Attempt to open the log file. If it fails (e.g. Docker volume permission issues), gracefully fallback to STDOUT only.
-    match std::fs::File::create("data/app.log") {
-        Ok(log_file) => {
-            tracing_subscriber::registry()
-                .with(tracing_subscriber::EnvFilter::new(
-                    std::env::var("RUST_LOG").unwrap_or_else(|_| "media_dashboard=info,tower_http=info,axum=info".into()),
-                ))
-                .with(tracing_subscriber::fmt::layer())
-                .with(tracing_subscriber::fmt::layer().with_writer(log_file))
-                .init();
-        },
-        Err(e) => {
-            tracing_subscriber::registry()
-                .with(tracing_subscriber::EnvFilter::new(
-                    std::env::var("RUST_LOG").unwrap_or_else(|_| "media_dashboard=info,tower_http=info,axum=info".into()),
-                ))
-                .with(tracing_subscriber::fmt::layer())
-                .init();
-            tracing::warn!("Failed to create data/app.log file: {}. Continuing with console-only logging. Ensure the volume permissions allow writing by the 'dashboard' user.", e);
-        }
-    }
+
+    // Read before the logger starts, since they also say how the log file rotates
+    let overrides = overrides::Overrides::load(cli.config.as_deref()).unwrap_or_else(|e| {
+        eprintln!("Failed to read configuration overrides: {}", e);
+        std::process::exit(1);
+    });
+    let log_settings = logging::LogSettings::from_settings(|key| overrides.value(key)).unwrap_or_else(|e| {
+        eprintln!("Invalid log settings: {}", e);
+        std::process::exit(1);
+    });
+    let log_feed = logging::init(&log_settings);
 
     tracing::info!("STAGE 1: Logger initialized (Console + File)");
 
+    let listen = server::Listen::parse(overrides.value("listen_address"), overrides.value("port"));
+    let base_path = server::normalize_base_path(overrides.value("base_path"));
+    let (listen, base_path) = match (listen, base_path) {
+        (Ok(listen), Ok(base_path)) => (listen, base_path),
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::error!("Invalid listener settings: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let proxy_auth = auth::proxy::ProxyAuth::from_settings(|key| overrides.value(key)).unwrap_or_else(|e| {
+        tracing::error!("Invalid forward authentication settings: {}", e);
+        std::process::exit(1);
+    });
+    let trusted_proxies = auth::proxy::TrustedProxies::from_settings(|key| overrides.value(key)).unwrap_or_else(|e| {
+        tracing::error!("Invalid trusted proxy settings: {}", e);
+        std::process::exit(1);
+    });
+    let data_dir = std::path::Path::new(&db::database_path()).parent().map(|p| p.to_path_buf()).unwrap_or_default();
+    let tls_files = server::tls::TlsFiles::resolve(
+        overrides.value("tls_cert"),
+        overrides.value("tls_key"),
+        overrides.value("tls_self_signed") == Some("true"),
+        &data_dir,
+    )
+    .and_then(|files| match (&listen, files) {
+        (server::Listen::Unix(_), Some(_)) => Err("TLS is not supported on a Unix socket; terminate it in the proxy".to_string()),
+        (_, files) => Ok(files),
+    })
+    .unwrap_or_else(|e| {
+        tracing::error!("Invalid TLS settings: {}", e);
+        std::process::exit(1);
+    });
+    let tls = match &tls_files {
+        Some(files) => Some(files.load().await.unwrap_or_else(|e| {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        })),
+        None => None,
+    };
+
     tracing::info!("STAGE 2: Initializing DB");
-    let db = db::init_db().await;
+    let db = db::init_db().await.unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    });
     
+    let secret_key = secrets::SecretKey::load_or_create(&db::database_path()).unwrap_or_else(|e| {
+        tracing::error!("Failed to load secret key: {}", e);
+        std::process::exit(1);
+    });
+
     tracing::info!("STAGE 3: Running migrations");
-    migrate_config_if_needed(&db).await;
+    migrate_config_if_needed(&db, &secret_key).await;
+    if let Err(e) = migrate_legacy_settings(&db, &secret_key).await {
+        tracing::error!("Failed to migrate service settings: {}", e);
+        std::process::exit(1);
+    }
+    migrate_dashboard_account(&db).await;
+    create_admin_from_overrides(&db, &overrides).await;
+
+    if cli.command.as_deref() == Some("rotate-key") {
+        if let Err(e) = rotate_secret_key(&db, &secret_key).await {
+            tracing::error!("Key rotation failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(command) = cli.command {
+        tracing::error!("Unknown command '{}'", command);
+        std::process::exit(2);
+    }
     
     tracing::info!("STAGE 4: Loading config");
-    let config = load_config_from_db(&db).await;
+    let mut config = load_config_from_db(&db, &secret_key).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to decrypt stored secrets: {}", e);
+        std::process::exit(1);
+    });
+    if !overrides.is_empty() {
+        tracing::info!("{} setting(s) pinned by the environment or config file", overrides.len());
+    }
+    overrides.apply(&mut config);
     let client = Client::builder()
         .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
         .build()
         .unwrap_or_else(|_| Client::new());
     let state = Arc::new(AppState { 
         config: Arc::new(tokio::sync::RwLock::new(config)), 
-        client, 
-        db 
+        client: client.clone(),
+        db,
+        snapshot: Arc::new(tokio::sync::RwLock::new(poller::Snapshot::default())),
+        refresh: Arc::new(tokio::sync::Notify::new()),
+        events: events::channel(),
+        secrets: Arc::new(secret_key),
+        overrides: Arc::new(overrides),
+        proxy_auth: proxy_auth.map(Arc::new),
+        trusted_proxies: Arc::new(trusted_proxies),
+        base_path: base_path.clone(),
+        tls: tls.is_some(),
+        oidc: Arc::new(auth::oidc::Oidc::new(client.clone())),
+        lockout: Arc::new(tokio::sync::Mutex::new(auth::lockout::LoginThrottle::default())),
+        verified_auth: Arc::new(tokio::sync::RwLock::new(HashMap::new())),
+        log_feed,
     });
 
-    tracing::info!("STAGE 5: Setting up router");
-    let app = Router::new()
-        // Dashboard status
+    tracing::info!("STAGE 5: Starting background poller");
+    poller::spawn(state.clone());
+    poller::spawn_torrent_watch(state.clone());
+    audit::retention::spawn(state.clone(), data_dir);
+
+    tracing::info!("STAGE 6: Setting up router");
+    // Reachable without a role check: setup, login and who is signed in
+    let public = Router::new()
         .route("/api/needs-setup", get(needs_setup_handler))
         .route("/api/setup", post(setup_handler))
+        .route("/api/login", post(login_handler))
+        .route("/api/login/options", get(login_options))
+        .route("/api/oidc/login", get(oidc_login))
+        .route("/api/oidc/callback", get(oidc_callback))
+        .route("/api/session", get(session_info));
+
+    let viewer = Router::new()
+        // Dashboard status
         .route("/api/status", get(get_all_status))
-        .route("/api/search", get(global_search))
+        .route("/api/services", get(list_services))
+        .route("/api/uptime", get(get_uptime_summary))
+        .route("/api/uptime/:service", get(get_service_uptime))
+        .route("/api/events", get(event_stream))
         .route("/api/calendar", get(get_calendar_data))
         .route("/api/stats", get(get_library_stats))
-        .route("/api/config", get(get_dashboard_config).post(update_dashboard_config))
-        .route("/api/settings/:service", get(get_service_settings).post(update_service_settings))
-        .route("/api/logs/audit", get(get_audit_logs))
-        .route("/api/logs/system", get(get_system_logs))
+        // Libraries
+        .route("/api/sonarr/series", get(sonarr_list_series))
+        .route("/api/sonarr/series/:id/seasons", get(sonarr_list_seasons))
+        .route("/api/sonarr/series/:id/episodes", get(sonarr_list_episodes))
+        .route("/api/sonarr/series/:id/files", get(sonarr_episode_files))
+        .route("/api/radarr/movies", get(radarr_list_movies))
+        .route("/api/radarr/movies/:id/files", get(radarr_movie_files))
+        .route("/api/radarr/movies/:id/history", get(radarr_movie_history))
+        .route("/api/lidarr/artists", get(lidarr_list_artists))
+        .route("/api/lidarr/status", get(lidarr_get_status))
+        .route("/api/plex/libraries", get(plex_get_libraries))
+        .route("/api/plex/recently-added", get(plex_recently_added))
+        .route("/api/plex/server-info", get(plex_server_info))
+        .route("/api/jackett/indexers", get(jackett_list_indexers))
+        .route("/api/prowlarr/indexers", get(prowlarr_list_indexers))
+        .route("/api/prowlarr/status", get(prowlarr_get_status))
+        .route("/api/transmission/torrents", get(transmission_list_torrents))
+        .route_layer(axum::middleware::from_fn_with_state(auth::Access::new(auth::Role::Viewer, Some(auth::tokens::Scope::StatusRead)), require_role));
+
+    // Each signed-in user's own password, session and API tokens; not reachable with a token
+    let account = Router::new()
+        .route("/api/session/password", post(change_own_password))
+        .route("/api/logout", post(logout_handler))
+        .route("/api/tokens", get(list_api_tokens).post(create_api_token))
+        .route("/api/tokens/:id", delete(delete_api_token))
+        .route_layer(axum::middleware::from_fn_with_state(auth::Access::new(auth::Role::Viewer, None), require_role));
+
+    let media = Router::new()
+        .route("/api/search", get(global_search))
         // Sonarr CRUD
-        .route("/api/sonarr/series", get(sonarr_list_series).post(sonarr_add_series))
+        .route("/api/sonarr/series", post(sonarr_add_series))
         .route("/api/sonarr/series/search", get(sonarr_search_series))
         .route("/api/sonarr/series/:id", delete(sonarr_delete_series))
+        .route("/api/sonarr/series/:id/search", post(sonarr_series_search))
+        .route("/api/sonarr/series/:id/seasons/:season", post(sonarr_monitor_season))
+        .route("/api/sonarr/series/:id/seasons/:season/search", post(sonarr_season_search))
+        .route("/api/sonarr/series/:id/episodes/:episode", post(sonarr_monitor_episode))
+        .route("/api/sonarr/series/:id/episodes/:episode/search", post(sonarr_episode_search))
         .route("/api/sonarr/rootfolders", get(sonarr_root_folders))
         .route("/api/sonarr/qualityprofiles", get(sonarr_quality_profiles))
         // Radarr CRUD
-        .route("/api/radarr/movies", get(radarr_list_movies).post(radarr_add_movie))
+        .route("/api/radarr/movies", post(radarr_add_movie))
         .route("/api/radarr/movies/search", get(radarr_search_movies))
-        .route("/api/radarr/movies/:id", delete(radarr_delete_movie))
+        .route("/api/radarr/movies/:id", post(radarr_edit_movie).delete(radarr_delete_movie))
+        .route("/api/radarr/movies/:id/search", post(radarr_movie_search))
+        .route("/api/radarr/movies/:id/refresh", post(radarr_refresh_movie))
         .route("/api/radarr/rootfolders", get(radarr_root_folders))
         .route("/api/radarr/qualityprofiles", get(radarr_quality_profiles))
-        // Jackett
-        .route("/api/jackett/indexers", get(jackett_list_indexers))
-        // Plex
-        .route("/api/plex/libraries", get(plex_get_libraries))
-        .route("/api/plex/recently-added", get(plex_recently_added))
-        .route("/api/plex/server-info", get(plex_server_info))
-        // Prowlarr
-        .route("/api/prowlarr/indexers", get(prowlarr_list_indexers))
-        .route("/api/prowlarr/status", get(prowlarr_get_status))
-        // Lidarr
-        .route("/api/lidarr/artists", get(lidarr_list_artists))
-        .route("/api/lidarr/status", get(lidarr_get_status))
-        // Transmission CRUD
-        .route("/api/transmission/torrents", get(transmission_list_torrents).post(transmission_add_torrent))
+        .route_layer(axum::middleware::from_fn_with_state(auth::Access::new(auth::Role::Operator, Some(auth::tokens::Scope::MediaWrite)), require_role));
+
+    let torrents = Router::new()
+        .route("/api/transmission/torrents", post(transmission_add_torrent))
         .route("/api/transmission/torrents/:id", delete(transmission_remove_torrent))
         .route("/api/transmission/torrents/:id/start", post(transmission_start_torrent))
         .route("/api/transmission/torrents/:id/stop", post(transmission_stop_torrent))
+        .route_layer(axum::middleware::from_fn_with_state(auth::Access::new(auth::Role::Operator, Some(auth::tokens::Scope::TorrentsControl)), require_role));
+
+    let admin = Router::new()
+        .route("/api/config", get(get_dashboard_config).post(update_dashboard_config))
+        .route("/api/config/export", post(export_config))
+        .route("/api/config/import", post(import_config))
+        .route("/api/settings/:service", get(get_service_settings).post(update_service_settings))
+        .route("/api/users", get(list_users).post(create_user))
+        .route("/api/users/:id", post(update_user).delete(delete_user))
+        .route("/api/logout/all", post(logout_all_handler))
+        .route("/api/logs/audit", get(get_audit_logs))
+        .route("/api/logs/audit/export", get(export_audit_logs))
+        .route("/api/logs/logins", get(get_login_logs))
+        .route("/api/logs/system", get(get_system_logs))
+        .route("/api/logs/system/follow", get(follow_system_logs))
+        .route_layer(axum::middleware::from_fn_with_state(auth::Access::new(auth::Role::Admin, None), require_role));
+
+    let app = Router::new()
+        .merge(public)
+        .merge(viewer)
+        .merge(account)
+        .merge(media)
+        .merge(torrents)
+        .merge(admin)
         // Static files
         .fallback_service(ServeDir::new("static"))
         .layer(axum::middleware::from_fn_with_state(state.clone(), auth_middleware))
         .with_state(state);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 7778));
-    tracing::debug!("listening on {}", addr);
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    if let (Some(config), Some(files)) = (&tls, tls_files) {
+        server::tls::spawn_reload(config.clone(), files);
+    }
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    tracing::info!("Listening on {} ({}, base path {}/)", listen, scheme, base_path);
+    if let Err(e) = server::serve(&listen, server::mount(app, &base_path), tls).await {
+        tracing::error!("Failed to listen on {}: {}", listen, e);
+        std::process::exit(1);
+    }
 }
 
 // ===================== Dashboard Handlers =====================
@@ -279,388 +795,1551 @@ async fn main() {
 async fn needs_setup_handler(
     State(state): State<Arc<AppState>>,
 ) -> Json<serde_json::Value> {
-    let config = state.config.read().await;
-    let needs = config.dashboard_user.is_empty() || config.dashboard_pass.is_empty();
+    let needs = db::count_users(&state.db).await == 0;
     Json(serde_json::json!({ "needs_setup": needs }))
 }
 
+/// With OIDC configured the admin may be created without a password; their account is then
+/// linked to the first provider account that signs in under its name.
 async fn setup_handler(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<Config>,
-) -> axum::http::StatusCode {
-    {
-        let mut config = state.config.write().await;
-        if !config.dashboard_user.is_empty() && !config.dashboard_pass.is_empty() {
-            return axum::http::StatusCode::FORBIDDEN;
-        }
-        if payload.dashboard_user.is_empty() || payload.dashboard_pass.is_empty() {
-            return axum::http::StatusCode::BAD_REQUEST;
+    Json(payload): Json<SetupPayload>,
+) -> Result<axum::http::StatusCode, AppError> {
+    let mut config = payload.config;
+    state.overrides.apply(&mut config);
+    let sso = config.oidc.is_enabled();
+    if payload.dashboard_user.is_empty() || (payload.dashboard_pass.is_empty() && !sso) {
+        return Err((StatusCode::BAD_REQUEST, "Username and password are required".to_string()));
+    }
+    validate_config(&config)?;
+    if sso {
+        state.oidc.discover(&config.oidc.issuer).await.map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    let passwordless = payload.dashboard_pass.is_empty();
+    let password_hash = match payload.dashboard_pass.as_str() {
+        "" => unusable_password_hash().await?,
+        _ => hash_password(payload.dashboard_pass).await?,
+    };
+    if !db::create_first_user(&state.db, &payload.dashboard_user, &password_hash).await {
+        return Err((StatusCode::FORBIDDEN, "Setup has already been completed".to_string()));
+    }
+    if passwordless {
+        // Linked to the provider account that first signs in under this name
+        if let Some(admin) = db::find_user(&state.db, &payload.dashboard_user).await {
+            db::set_oidc_link(&state.db, admin.id, Some(&config.oidc.issuer_id())).await.map_err(internal_err)?;
         }
-        *config = payload.clone();
     }
-    
-    save_config_to_db(&state.db, &payload).await;
-    db::log_event(&state.db, "System", "Setup", "Initial mandatory setup completed").await;
-    axum::http::StatusCode::OK
+
+    *state.config.write().await = config.clone();
+    if let Err(e) = persist_config(&state, &config).await {
+        tracing::error!("Failed to save setup configuration: {}", e);
+        return Err(internal_err(e));
+    }
+    state.refresh.notify_one();
+    let method = if sso { " (single sign-on)" } else { "" };
+    audit(&state, None, "System", "Setup", &format!("Initial mandatory setup completed, admin '{}' created{}", payload.dashboard_user, method)).await;
+    Ok(axum::http::StatusCode::OK)
 }
 
-async fn get_all_status(
+async fn login_handler(
     State(state): State<Arc<AppState>>,
-) -> Json<Vec<ServiceStatus>> {
-    let mut statuses = Vec::new();
-    let config = state.config.read().await;
-    let client = &state.client;
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: axum::http::HeaderMap,
+    Json(payload): Json<LoginPayload>,
+) -> Result<Response, Response> {
+    let user = authenticate(&state, client_ip(&state, addr, &headers), &payload.username, payload.password).await
+        .map_err(IntoResponse::into_response)?;
+    let cookie = start_session(&state, &user.username, &headers).await.map_err(IntoResponse::into_response)?;
+    Ok(([(header::SET_COOKIE, cookie)], StatusCode::OK).into_response())
+}
 
-    if !config.plex_url.is_empty() {
-        statuses.push(api::plex::get_status(client, &config.plex_url, &config.plex_token).await);
+/// Creates a session for `username` and returns the cookie that carries it.
+async fn start_session(state: &AppState, username: &str, headers: &axum::http::HeaderMap) -> Result<String, AppError> {
+    let hours = state.config.read().await.session_lifetime_hours;
+    let lifetime = i64::try_from(hours).ok().and_then(chrono::TimeDelta::try_hours);
+    let expires = lifetime.and_then(|lifetime| Some((lifetime, chrono::Utc::now().checked_add_signed(lifetime)?)));
+    let Some((lifetime, expires)) = expires else {
+        return Err(internal_err(format!("Session lifetime of {} hours is out of range", hours)));
+    };
+    let token = auth::new_session_token();
+    let user_agent = headers.get(header::USER_AGENT).and_then(|h| h.to_str().ok());
+    db::prune_sessions(&state.db).await;
+    if !db::create_session(&state.db, &auth::session_id(&token), username, expires, user_agent).await {
+        return Err(internal_err("Failed to create session"));
     }
-    if !config.sonarr_url.is_empty() {
-        statuses.push(api::sonarr::get_status(client, &config.sonarr_url, &config.sonarr_key).await);
+    Ok(auth::session_cookie(&token, lifetime.num_seconds(), &state.base_path, state.tls))
+}
+
+/// A hash no password will match, for accounts that only sign in through the OIDC provider.
+async fn unusable_password_hash() -> Result<String, AppError> {
+    hash_password(auth::new_session_token()).await
+}
+
+/// Which sign-in methods the login page should offer.
+async fn login_options(
+    State(state): State<Arc<AppState>>,
+) -> Json<serde_json::Value> {
+    let oidc = state.config.read().await.oidc.is_enabled();
+    Json(serde_json::json!({ "oidc": oidc }))
+}
+
+#[derive(Deserialize)]
+struct OidcLoginQuery {
+    next: Option<String>,
+}
+
+/// Sends the browser to the OIDC provider to sign in.
+async fn oidc_login(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: axum::http::HeaderMap,
+    Query(query): Query<OidcLoginQuery>,
+) -> Response {
+    let settings = state.config.read().await.oidc.clone();
+    if !settings.is_enabled() {
+        return (StatusCode::NOT_FOUND, "Single sign-on is not configured").into_response();
     }
-    if !config.radarr_url.is_empty() {
-        statuses.push(api::radarr::get_status(client, &config.radarr_url, &config.radarr_key).await);
+    let redirect_uri = if settings.redirect_url.trim().is_empty() {
+        let Some(origin) = request_origin(&state, addr, &headers) else {
+            return (StatusCode::BAD_REQUEST, "Cannot tell the dashboard's URL; set the OIDC redirect URL").into_response();
+        };
+        format!("{}{}/api/oidc/callback", origin, state.base_path)
+    } else {
+        settings.redirect_url.trim().to_string()
+    };
+    // Only same-origin paths, so the callback cannot be used as an open redirect
+    let next = query.next
+        .filter(|next| next.starts_with('/') && !next.starts_with("//") && !next.contains('\\'))
+        .unwrap_or_else(|| format!("{}/", state.base_path));
+
+    match state.oidc.begin(&settings, redirect_uri, next).await {
+        Ok((url, login_state)) => {
+            let cookie = auth::oidc::state_cookie(&login_state, auth::oidc::state_cookie_max_age(), &state.base_path, state.tls);
+            ([(header::SET_COOKIE, cookie)], axum::response::Redirect::to(&url)).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to start OIDC login: {}", e);
+            login_error(&state, "The single sign-on provider could not be reached")
+        }
     }
-    if !config.jackett_url.is_empty() {
-        statuses.push(api::jackett::get_status(client, &config.jackett_url, &config.jackett_key).await);
+}
+
+/// The scheme and host the browser reached the dashboard at. The `X-Forwarded-Proto` and
+/// `X-Forwarded-Host` headers are only believed from a trusted proxy; anyone else could
+/// point the login at a host of their choosing.
+fn request_origin(state: &AppState, peer: SocketAddr, headers: &axum::http::HeaderMap) -> Option<String> {
+    // The nearest proxy's value, in case one further out only appended to the header
+    let header = |name: &str| {
+        headers.get(name)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    let own_scheme = if state.tls { "https" } else { "http" };
+    let (scheme, host) = if state.trusted_proxies.contains(peer.ip()) {
+        let scheme = header("x-forwarded-proto").filter(|s| matches!(*s, "http" | "https")).unwrap_or(own_scheme);
+        (scheme, header("x-forwarded-host").or_else(|| header("host"))?)
+    } else {
+        (own_scheme, header("host")?)
+    };
+    // A host name with an optional port, nothing that could end the authority early
+    let valid = host.chars().all(|c| c.is_ascii_alphanumeric() || "-.:[]".contains(c));
+    Some(format!("{}://{}", scheme, host)).filter(|_| valid)
+}
+
+#[derive(Deserialize)]
+struct OidcCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Where the provider sends the browser back. Verifies the login, maps the user's groups
+/// to a role and starts a session. A linked dashboard user keeps their own role; accounts
+/// created here follow their groups at every login.
+async fn oidc_callback(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: axum::http::HeaderMap,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Response {
+    let clear = [(header::SET_COOKIE, auth::oidc::clear_state_cookie(&state.base_path, state.tls))];
+    if let Some(error) = query.error {
+        let reason = query.error_description.unwrap_or(error);
+        return (clear, login_error(&state, &format!("Sign-in was cancelled: {}", reason))).into_response();
     }
-    if !config.transmission_url.is_empty() {
-        statuses.push(api::transmission::get_status(client, &config.transmission_url, &config.transmission_user, &config.transmission_pass).await);
+    let (Some(code), Some(login_state)) = (query.code, query.state) else {
+        return (clear, login_error(&state, "Incomplete response from the sign-in provider")).into_response();
+    };
+    let cookie_state = auth::read_cookie(&headers, auth::oidc::STATE_COOKIE).unwrap_or_default();
+    if !auth::constant_time_eq(cookie_state.as_bytes(), login_state.as_bytes()) {
+        return (clear, login_error(&state, "This sign-in was started in another browser; please try again")).into_response();
     }
-    if !config.jellyfin_url.is_empty() {
-        statuses.push(api::jellyfin::get_status(client, &config.jellyfin_url, &config.jellyfin_key).await);
+
+    let settings = state.config.read().await.oidc.clone();
+    let sign_in = match state.oidc.finish(&settings, &login_state, &code).await {
+        Ok(sign_in) => sign_in,
+        Err(e) => {
+            tracing::warn!("OIDC login from {} failed: {}", client_label(client_ip(&state, addr, &headers)), e);
+            return (clear, login_error(&state, "Sign-in failed; see the dashboard log for details")).into_response();
+        }
+    };
+
+    let ip = client_label(client_ip(&state, addr, &headers));
+    let role = match auth::oidc::Oidc::role(&settings, &sign_in.groups) {
+        Ok(Some(role)) => role,
+        Ok(None) => {
+            db::log_login(&state.db, &sign_in.username, &ip, false).await;
+            let message = format!("'{}' has no dashboard role", sign_in.username);
+            return (clear, login_error(&state, &message)).into_response();
+        }
+        Err(e) => return (clear, login_error(&state, &e)).into_response(),
+    };
+    let username = match oidc_account(&state, &sign_in, role).await {
+        Ok(username) => username,
+        Err(OidcAccountError::Taken) => {
+            db::log_login(&state.db, &sign_in.username, &ip, false).await;
+            tracing::warn!("OIDC login of '{}' (subject {}) refused: the username belongs to another account", sign_in.username, sign_in.subject);
+            let message = format!("The username '{}' belongs to another dashboard account", sign_in.username);
+            return (clear, login_error(&state, &message)).into_response();
+        }
+        Err(OidcAccountError::Failed(e)) => {
+            tracing::error!("Failed to sign in '{}' by OIDC: {}", sign_in.username, e);
+            return (clear, login_error(&state, "Sign-in failed; see the dashboard log for details")).into_response();
+        }
+    };
+    let cookie = start_session(&state, &username, &headers).await;
+    match cookie {
+        Ok(cookie) => {
+            db::log_login(&state.db, &username, &ip, true).await;
+            let cookies = axum::response::AppendHeaders([
+                (header::SET_COOKIE, cookie),
+                (header::SET_COOKIE, auth::oidc::clear_state_cookie(&state.base_path, state.tls)),
+            ]);
+            (cookies, axum::response::Redirect::to(&sign_in.next)).into_response()
+        }
+        Err((_, e)) => {
+            tracing::error!("Failed to sign in '{}' by OIDC: {}", username, e);
+            (clear, login_error(&state, "Sign-in failed; see the dashboard log for details")).into_response()
+        }
     }
-    if !config.emby_url.is_empty() {
-        statuses.push(api::emby::get_status(client, &config.emby_url, &config.emby_key).await);
+}
+
+enum OidcAccountError {
+    /// The username claim names an account that is not bound to this subject.
+    Taken,
+    Failed(String),
+}
+
+/// The account of the user the provider signed in, created on their first login, with
+/// its role brought in line with their groups. Accounts are found by issuer and subject,
+/// never by the username claim alone, so a matching name cannot take over a local account.
+async fn oidc_account(state: &AppState, sign_in: &auth::oidc::SignIn, role: auth::Role) -> Result<String, OidcAccountError> {
+    let failed = |e: sqlx::Error| OidcAccountError::Failed(e.to_string());
+    let user = match db::find_oidc_user(&state.db, &sign_in.issuer, &sign_in.subject).await {
+        Some(user) => user,
+        None => match db::find_user(&state.db, &sign_in.username).await {
+            // Awaiting its link, or created by single sign-on before subjects were recorded
+            Some(user) if db::bind_oidc_subject(&state.db, user.id, &sign_in.issuer, &sign_in.subject).await => {
+                let event = db::AuditEvent::new("System", "User Updated", format!("'{}' linked to single sign-on", user.username))
+                    .target(&user.username)
+                    .after(serde_json::json!({ "oidc_issuer": sign_in.issuer, "oidc_subject": sign_in.subject }));
+                audit_event(state, None, event).await;
+                user
+            }
+            Some(_) => return Err(OidcAccountError::Taken),
+            None => {
+                let hash = unusable_password_hash().await.map_err(|(_, e)| OidcAccountError::Failed(e))?;
+                db::delete_user_api_tokens(&state.db, &sign_in.username).await;
+                if !db::create_oidc_user(&state.db, &sign_in.username, &hash, role.as_str(), &sign_in.issuer, &sign_in.subject).await {
+                    return Err(OidcAccountError::Taken);
+                }
+                db::log_event(&state.db, None, "System", "User Created", &format!("'{}' created as {} by single sign-on", sign_in.username, role.as_str())).await;
+                return Ok(sign_in.username.clone());
+            }
+        },
+    };
+    // Accounts managed here keep the role set on the Settings page
+    if user.managed_by == "oidc" && user.role != role.as_str() {
+        db::set_user_role(&state.db, user.id, role.as_str()).await.map_err(failed)?;
     }
-    if !config.prowlarr_url.is_empty() {
-        statuses.push(api::prowlarr::get_status(client, &config.prowlarr_url, &config.prowlarr_key).await);
+    Ok(user.username)
+}
+
+/// Back to the login page, which shows `message`.
+fn login_error(state: &AppState, message: &str) -> Response {
+    let location = format!("{}/login.html?error={}", state.base_path, urlencoding::encode(message));
+    axum::response::Redirect::to(&location).into_response()
+}
+
+async fn session_info(
+    axum::Extension(identity): axum::Extension<auth::Identity>,
+) -> Json<auth::Identity> {
+    Json(identity)
+}
+
+async fn change_own_password(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: axum::http::HeaderMap,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Json(payload): Json<PasswordChangePayload>,
+) -> Result<axum::http::StatusCode, Response> {
+    if payload.new_password.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Password must not be empty").into_response());
     }
-    if !config.lidarr_url.is_empty() {
-        statuses.push(api::lidarr::get_status(client, &config.lidarr_url, &config.lidarr_key).await);
+    authenticate(&state, client_ip(&state, addr, &headers), &user.username, payload.current_password).await
+        .map_err(IntoResponse::into_response)?;
+    let password_hash = hash_password(payload.new_password).await.map_err(IntoResponse::into_response)?;
+    db::set_user_password(&state.db, &user.username, &password_hash, None).await;
+    db::delete_user_sessions(&state.db, &user.username).await;
+    let event = db::AuditEvent::new("System", "Password Changed", format!("User '{}' changed their password", user.username))
+        .target(&user.username);
+    audit_event(&state, Some(&user), event).await;
+    Ok(axum::http::StatusCode::OK)
+}
+
+async fn logout_handler(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+) -> Response {
+    if let Some(token) = auth::read_cookie(&headers, auth::SESSION_COOKIE) {
+        db::delete_session(&state.db, &auth::session_id(token)).await;
     }
+    ([(header::SET_COOKIE, auth::clear_session_cookie(&state.base_path, state.tls))], StatusCode::OK).into_response()
+}
 
-    Json(statuses)
+async fn logout_all_handler(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+) -> Response {
+    let ended = db::delete_all_sessions(&state.db).await;
+    audit(&state, Some(&user), "System", "Sessions Revoked", &format!("Signed out {} session(s)", ended)).await;
+    ([(header::SET_COOKIE, auth::clear_session_cookie(&state.base_path, state.tls))], StatusCode::OK).into_response()
 }
 
-async fn global_search(
+// ===================== User Management Handlers =====================
+
+async fn list_users(
     State(state): State<Arc<AppState>>,
-    Query(q): Query<SearchQuery>,
-) -> Result<Json<serde_json::Value>, AppError> {
-    let term = q.term.unwrap_or_default();
-    if term.is_empty() {
-        return Err((axum::http::StatusCode::BAD_REQUEST, "Missing 'term' parameter".to_string()));
+) -> Result<Json<Vec<db::User>>, AppError> {
+    let users = db::list_users(&state.db).await.map_err(internal_err)?;
+    Ok(Json(users))
+}
+
+async fn create_user(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Json(payload): Json<NewUserPayload>,
+) -> Result<axum::http::StatusCode, AppError> {
+    let username = payload.username.trim();
+    if username.is_empty() || payload.password.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Username and password are required".to_string()));
     }
-    let config = state.config.read().await;
-    let client = &state.client;
+    if db::find_user(&state.db, username).await.is_some() {
+        return Err((StatusCode::CONFLICT, format!("User '{}' already exists", username)));
+    }
+    let password_hash = hash_password(payload.password).await?;
+    // Tokens left from before API tokens required an account must not take on the new one
+    db::delete_user_api_tokens(&state.db, username).await;
+    db::create_user(&state.db, username, &password_hash, payload.role.as_str()).await.map_err(internal_err)?;
+    let event = db::AuditEvent::new("System", "User Created", format!("User '{}' created with role {}", username, payload.role.as_str()))
+        .target(username)
+        .after(serde_json::json!({ "role": payload.role.as_str() }));
+    audit_event(&state, Some(&user), event).await;
+    Ok(axum::http::StatusCode::CREATED)
+}
 
-    let mut sonarr_results = serde_json::Value::Null;
-    let mut radarr_results = serde_json::Value::Null;
+async fn update_user(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path(id): Path<i64>,
+    Json(payload): Json<UserUpdatePayload>,
+) -> Result<axum::http::StatusCode, AppError> {
+    let target = db::find_user_by_id(&state.db, id).await
+        .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
 
-    if !config.sonarr_url.is_empty() {
-        if let Ok(res) = api::sonarr::search_series(client, &config.sonarr_url, &config.sonarr_key, &term).await {
-            sonarr_results = res;
+    if let Some(role) = payload.role {
+        if target.role == auth::Role::Admin.as_str() && role != auth::Role::Admin && db::count_admins(&state.db).await <= 1 {
+            return Err((StatusCode::CONFLICT, "Cannot demote the last admin".to_string()));
         }
+        db::set_user_role(&state.db, id, role.as_str()).await.map_err(internal_err)?;
+        let event = db::AuditEvent::new("System", "User Updated", format!("User '{}' role changed from {} to {}", target.username, target.role, role.as_str()))
+            .target(&target.username)
+            .before(serde_json::json!({ "role": target.role }))
+            .after(serde_json::json!({ "role": role.as_str() }));
+        audit_event(&state, Some(&user), event).await;
     }
-    if !config.radarr_url.is_empty() {
-        if let Ok(res) = api::radarr::search_movies(client, &config.radarr_url, &config.radarr_key, &term).await {
-            radarr_results = res;
+    if let Some(password) = payload.password.filter(|p| !p.is_empty()) {
+        let password_hash = hash_password(password).await?;
+        db::set_user_password(&state.db, &target.username, &password_hash, None).await;
+        db::delete_user_sessions(&state.db, &target.username).await;
+        let event = db::AuditEvent::new("System", "User Updated", format!("Password of '{}' reset", target.username))
+            .target(&target.username);
+        audit_event(&state, Some(&user), event).await;
+    }
+    if let Some(link) = payload.sso_link {
+        if target.managed_by == "oidc" {
+            return Err((StatusCode::CONFLICT, "Accounts created by single sign-on are always linked".to_string()));
         }
+        let issuer = match link {
+            true => Some(state.config.read().await.oidc.issuer_id()).filter(|issuer| !issuer.is_empty())
+                .ok_or((StatusCode::CONFLICT, "Single sign-on is not configured".to_string()))
+                .map(Some)?,
+            false => None,
+        };
+        db::set_oidc_link(&state.db, id, issuer.as_deref()).await.map_err(internal_err)?;
+        let details = match link {
+            true => format!("'{}' will be linked at their next single sign-on", target.username),
+            false => format!("Single sign-on link of '{}' removed", target.username),
+        };
+        let event = db::AuditEvent::new("System", "User Updated", details)
+            .target(&target.username)
+            .before(serde_json::json!({ "oidc_issuer": target.oidc_issuer, "oidc_subject": target.oidc_subject }))
+            .after(serde_json::json!({ "oidc_issuer": issuer, "oidc_subject": null }));
+        audit_event(&state, Some(&user), event).await;
     }
+    Ok(axum::http::StatusCode::OK)
+}
 
-    Ok(Json(serde_json::json!({
-        "sonarr": sonarr_results,
-        "radarr": radarr_results
-    })))
+async fn delete_user(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path(id): Path<i64>,
+) -> Result<axum::http::StatusCode, AppError> {
+    let target = db::find_user_by_id(&state.db, id).await
+        .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    if target.role == auth::Role::Admin.as_str() && db::count_admins(&state.db).await <= 1 {
+        return Err((StatusCode::CONFLICT, "Cannot delete the last admin".to_string()));
+    }
+    db::delete_user(&state.db, id).await.map_err(internal_err)?;
+    db::delete_user_sessions(&state.db, &target.username).await;
+    db::delete_user_api_tokens(&state.db, &target.username).await;
+    let event = db::AuditEvent::new("System", "User Deleted", format!("User '{}' deleted", target.username))
+        .target(&target.username)
+        .before(serde_json::json!({ "role": target.role, "managed_by": target.managed_by }));
+    audit_event(&state, Some(&user), event).await;
+    Ok(axum::http::StatusCode::OK)
 }
 
-async fn get_calendar_data(
+#[derive(Deserialize)]
+struct NewTokenPayload {
+    name: String,
+    scopes: Vec<auth::tokens::Scope>,
+    /// Days until the token expires, up to `auth::tokens::MAX_EXPIRY_DAYS`; it never does when absent.
+    expires_in_days: Option<u32>,
+}
+
+async fn list_api_tokens(
     State(state): State<Arc<AppState>>,
-) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    let client = &state.client;
-    
-    let now = chrono::Utc::now();
-    let end = now + chrono::Duration::days(7);
-    let start_str = now.format("%Y-%m-%d").to_string();
-    let end_str = end.format("%Y-%m-%d").to_string();
+    axum::Extension(user): axum::Extension<auth::Identity>,
+) -> Result<Json<Vec<db::ApiToken>>, AppError> {
+    db::list_api_tokens(&state.db, &user.username).await.map(Json).map_err(internal_err)
+}
+
+/// Creates a token for the signed-in user. The token is only ever returned here.
+async fn create_api_token(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Json(payload): Json<NewTokenPayload>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let name = payload.name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err((StatusCode::BAD_REQUEST, "A token needs a name of at most 64 characters".to_string()));
+    }
+    if payload.scopes.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A token needs at least one scope".to_string()));
+    }
+    // A token takes its role from its owner's account, which users signed in by the
+    // authenticating proxy may not have
+    if db::find_user(&state.db, &user.username).await.is_none() {
+        return Err((StatusCode::FORBIDDEN, "API tokens require a local account".to_string()));
+    }
+    if let Some(scope) = payload.scopes.iter().find(|scope| scope.min_role() > user.role) {
+        return Err((StatusCode::FORBIDDEN, format!("The {} scope requires the {} role", scope.as_str(), scope.min_role().as_str())));
+    }
+    let expires_at = match payload.expires_in_days {
+        Some(days) if !(1..=auth::tokens::MAX_EXPIRY_DAYS).contains(&days) => {
+            let message = format!("Expiry must be between 1 and {} days", auth::tokens::MAX_EXPIRY_DAYS);
+            return Err((StatusCode::BAD_REQUEST, message));
+        }
+        Some(days) => Some(chrono::Utc::now() + chrono::Duration::days(days as i64)),
+        None => None,
+    };
 
-    let mut sonarr_cal = serde_json::Value::Null;
-    let mut radarr_cal = serde_json::Value::Null;
+    let token = auth::tokens::new_token();
+    let scopes = auth::tokens::format_scopes(&payload.scopes);
+    let id = db::create_api_token(&state.db, &user.username, name, &auth::tokens::token_hash(&token), &scopes, expires_at).await
+        .map_err(internal_err)?;
+    let expiry = expires_at.map_or("never expires".to_string(), |at| format!("expires {}", at.format("%Y-%m-%d")));
+    let event = db::AuditEvent::new("System", "Token Created", format!("API token '{}' created with {} ({})", name, scopes, expiry))
+        .target(id)
+        .title(name)
+        .after(serde_json::json!({ "scopes": scopes, "expires_at": expires_at.map(|at| at.to_rfc3339()) }));
+    audit_event(&state, Some(&user), event).await;
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "id": id,
+        "name": name,
+        "token": token,
+        "scopes": scopes,
+        "expires_at": expires_at.map(|at| at.to_rfc3339()),
+    }))))
+}
+
+async fn delete_api_token(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path(id): Path<i64>,
+) -> Result<axum::http::StatusCode, AppError> {
+    let name = db::delete_api_token(&state.db, id, &user.username).await
+        .ok_or((StatusCode::NOT_FOUND, "Token not found".to_string()))?;
+    let event = db::AuditEvent::new("System", "Token Revoked", format!("API token '{}' revoked", name))
+        .target(id)
+        .title(&name);
+    audit_event(&state, Some(&user), event).await;
+    Ok(axum::http::StatusCode::OK)
+}
 
-    if !config.sonarr_url.is_empty() {
-        if let Ok(res) = api::sonarr::get_calendar(client, &config.sonarr_url, &config.sonarr_key, &start_str, &end_str).await {
-            sonarr_cal = res;
+async fn get_all_status(
+    State(state): State<Arc<AppState>>,
+) -> Json<serde_json::Value> {
+    let snapshot = state.snapshot.read().await;
+    Json(serde_json::json!({
+        "as_of": snapshot.as_of,
+        "services": snapshot.statuses,
+    }))
+}
+
+async fn event_stream(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    // Audit entries go only to subscribers who could read `/api/logs/audit`, which refuses tokens
+    let sees_audit = user.role == auth::Role::Admin && user.token.is_none();
+    // Subscribe before reading the snapshot so no update slips in between
+    let updates = BroadcastStream::new(state.events.subscribe())
+        .filter_map(|event| event.ok())
+        .filter(move |event| sees_audit || !event.admin_only());
+    let current = {
+        let snapshot = state.snapshot.read().await;
+        events::DashboardEvent::Status {
+            as_of: snapshot.as_of,
+            services: snapshot.statuses.clone(),
         }
+    };
+    let stream = tokio_stream::once(current)
+        .chain(updates)
+        .map(|event| event.to_sse());
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+async fn list_services(
+    State(state): State<Arc<AppState>>,
+) -> Json<serde_json::Value> {
+    let instances = state.config.read().await.all_instances();
+    let services: Vec<_> = instances
+        .iter()
+        .filter_map(|instance| Some((instance, instance.descriptor()?)))
+        .filter(|(instance, descriptor)| descriptor.is_configured(&instance.settings))
+        .map(|(instance, descriptor)| {
+            let client = (descriptor.connect)(&state.client, &instance.settings);
+            serde_json::json!({
+                "id": instance.id,
+                "kind": instance.kind,
+                "name": instance.label_or(client.name()),
+                "url": client.url(),
+                "capabilities": client.capabilities(),
+            })
+        })
+        .collect();
+    Json(serde_json::Value::Array(services))
+}
+
+/// Maps a `window` query value to its lookback period and the bucket width used for its bars.
+fn uptime_window(window: Option<&str>) -> Result<(chrono::Duration, i64), AppError> {
+    match window.unwrap_or("24h") {
+        "24h" => Ok((chrono::Duration::hours(24), 3600)),
+        "7d" => Ok((chrono::Duration::days(7), 6 * 3600)),
+        "30d" => Ok((chrono::Duration::days(30), 24 * 3600)),
+        other => Err((axum::http::StatusCode::BAD_REQUEST, format!("Unsupported window '{}', use 24h, 7d or 30d", other))),
     }
-    if !config.radarr_url.is_empty() {
-        if let Ok(res) = api::radarr::get_calendar(client, &config.radarr_url, &config.radarr_key, &start_str, &end_str).await {
-            radarr_cal = res;
+}
+
+fn uptime_pct(checks: i64, up_checks: i64) -> Option<f64> {
+    (checks > 0).then(|| up_checks as f64 * 100.0 / checks as f64)
+}
+
+/// Collapses consecutive failed checks into outage windows. An outage without an
+/// `end` is still ongoing at the latest check.
+fn outage_windows(checks: &[db::ServiceCheck]) -> Vec<serde_json::Value> {
+    let mut outages = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    for (i, check) in checks.iter().enumerate() {
+        match (&mut current, check.active) {
+            (None, false) => current = Some((i, i)),
+            (Some((_, last)), false) => *last = i,
+            (Some((first, last)), true) => {
+                outages.push(serde_json::json!({
+                    "start": checks[*first].timestamp,
+                    "end": check.timestamp,
+                    "checks": *last - *first + 1,
+                    "message": checks[*last].message,
+                }));
+                current = None;
+            }
+            (None, true) => {}
         }
     }
+    if let Some((first, last)) = current {
+        outages.push(serde_json::json!({
+            "start": checks[first].timestamp,
+            "end": null,
+            "checks": last - first + 1,
+            "message": checks[last].message,
+        }));
+    }
+    outages
+}
 
+async fn get_uptime_summary(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<UptimeQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let (window, _) = uptime_window(q.window.as_deref())?;
+    let summaries = db::uptime_summary(&state.db, chrono::Utc::now() - window)
+        .await.map_err(internal_err)?;
+    let services: Vec<_> = summaries.iter().map(|s| serde_json::json!({
+        "service": s.service,
+        "checks": s.checks,
+        "uptime_pct": uptime_pct(s.checks, s.up_checks),
+        "avg_latency_ms": s.avg_latency_ms,
+    })).collect();
     Ok(Json(serde_json::json!({
-        "sonarr": sonarr_cal,
-        "radarr": radarr_cal
+        "window": q.window.as_deref().unwrap_or("24h"),
+        "services": services,
     })))
 }
 
-async fn get_library_stats(
+async fn get_service_uptime(
     State(state): State<Arc<AppState>>,
+    Path(service): Path<String>,
+    Query(q): Query<UptimeQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
+    let (window, bucket_secs) = uptime_window(q.window.as_deref())?;
+    let since = chrono::Utc::now() - window;
+    let checks = db::service_checks(&state.db, &service, since).await.map_err(internal_err)?;
+    let series = db::check_series(&state.db, &service, since, bucket_secs).await.map_err(internal_err)?;
+
+    let up_checks = checks.iter().filter(|c| c.active).count() as i64;
+    let latencies: Vec<i64> = checks.iter().filter_map(|c| c.latency_ms).collect();
+    let avg_latency_ms = (!latencies.is_empty())
+        .then(|| latencies.iter().sum::<i64>() as f64 / latencies.len() as f64);
+    let series: Vec<_> = series.iter().map(|b| serde_json::json!({
+        "bucket_start": b.bucket_start,
+        "checks": b.checks,
+        "uptime_pct": uptime_pct(b.checks, b.up_checks),
+        "avg_latency_ms": b.avg_latency_ms,
+        "max_latency_ms": b.max_latency_ms,
+    })).collect();
+
+    Ok(Json(serde_json::json!({
+        "service": service,
+        "window": q.window.as_deref().unwrap_or("24h"),
+        "bucket_secs": bucket_secs,
+        "checks": checks.len(),
+        "uptime_pct": uptime_pct(checks.len() as i64, up_checks),
+        "avg_latency_ms": avg_latency_ms,
+        "outages": outage_windows(&checks),
+        "series": series,
+    })))
+}
+
+async fn global_search(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<SearchQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let term = q.term.unwrap_or_default();
+    if term.is_empty() {
+        return Err((axum::http::StatusCode::BAD_REQUEST, "Missing 'term' parameter".to_string()));
+    }
+    let instances = state.config.read().await.all_instances();
     let client = &state.client;
 
-    let mut sonarr_disk = serde_json::Value::Null;
-    let mut radarr_disk = serde_json::Value::Null;
+    let (sonarr_results, radarr_results) = tokio::join!(
+        api::merge_instances(&instances, "sonarr", |settings| {
+            api::sonarr::search_series(client, settings.get("sonarr_url"), settings.get("sonarr_key"), &term)
+        }),
+        api::merge_instances(&instances, "radarr", |settings| {
+            api::radarr::search_movies(client, settings.get("radarr_url"), settings.get("radarr_key"), &term)
+        }),
+    );
+
+    Ok(Json(serde_json::json!({
+        "sonarr": unique_by(sonarr_results, "tvdbId"),
+        "radarr": unique_by(radarr_results, "tmdbId")
+    })))
+}
+
+/// Every instance looks titles up in the same metadata source, so merged search
+/// results repeat; keep the first hit per external id.
+fn unique_by(results: serde_json::Value, key: &str) -> serde_json::Value {
+    let serde_json::Value::Array(items) = results else {
+        return results;
+    };
+    let mut seen = std::collections::HashSet::new();
+    items
+        .into_iter()
+        .filter(|item| item.get(key).is_none_or(|id| seen.insert(id.to_string())))
+        .collect()
+}
 
-    if !config.sonarr_url.is_empty() {
-        if let Ok(res) = api::sonarr::get_disk_space(client, &config.sonarr_url, &config.sonarr_key).await {
-            sonarr_disk = res;
+async fn get_calendar_data(
+    State(state): State<Arc<AppState>>,
+) -> Json<serde_json::Value> {
+    let snapshot = state.snapshot.read().await;
+    let mut body = snapshot.calendar.clone();
+    body["as_of"] = serde_json::json!(snapshot.as_of);
+    Json(body)
+}
+
+async fn get_library_stats(
+    State(state): State<Arc<AppState>>,
+) -> Json<serde_json::Value> {
+    let snapshot = state.snapshot.read().await;
+    let mut body = snapshot.disk_space.clone();
+    body["as_of"] = serde_json::json!(snapshot.as_of);
+    Json(body)
+}
+
+async fn get_dashboard_config(
+    State(state): State<Arc<AppState>>,
+) -> Json<serde_json::Value> {
+    let mut config = state.config.read().await.clone();
+    
+    let mask = "********".to_string();
+    let settings = std::iter::once(&mut config.services)
+        .chain(config.instances.iter_mut().map(|instance| &mut instance.settings));
+    for settings in settings {
+        for field in api::all_fields().filter(|f| f.kind == api::FieldKind::Secret) {
+            if !settings.get(field.key).is_empty() {
+                settings.set(field.key, mask.clone());
+            }
         }
     }
-    if !config.radarr_url.is_empty() {
-        if let Ok(res) = api::radarr::get_disk_space(client, &config.radarr_url, &config.radarr_key).await {
-            radarr_disk = res;
+    if !config.oidc.client_secret.is_empty() {
+        config.oidc.client_secret = mask.clone();
+    }
+    
+    let mut body = serde_json::to_value(config).unwrap_or_default();
+    // Settings the dashboard cannot change, each with the variable or file that sets it
+    body["read_only"] = serde_json::json!(state.overrides.sources());
+    Json(body)
+}
+
+async fn update_dashboard_config(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Json(mut payload): Json<Config>,
+) -> Result<axum::http::StatusCode, AppError> {
+    {
+        // Empty numbers keep their current value
+        let config = state.config.read().await;
+        if payload.poll_interval == 0 { payload.poll_interval = config.poll_interval; }
+        if payload.session_lifetime_hours == 0 { payload.session_lifetime_hours = config.session_lifetime_hours; }
+    }
+    validate_config(&payload)?;
+
+    let mask = "********";
+    let changes = {
+        let mut config = state.config.write().await;
+        
+        // Preserve existing keys if incoming payload has the mask
+        for field in api::all_fields().filter(|f| f.kind == api::FieldKind::Secret) {
+            if payload.services.get(field.key) == mask {
+                payload.services.set(field.key, config.services.get(field.key).to_string());
+            }
+            for instance in &mut payload.instances {
+                if instance.settings.get(field.key) == mask {
+                    let existing = config.instances.iter()
+                        .find(|old| old.id == instance.id && old.kind == instance.kind)
+                        .map(|old| old.settings.get(field.key).to_string())
+                        .unwrap_or_default();
+                    instance.settings.set(field.key, existing);
+                }
+            }
+        }
+        if payload.oidc.client_secret == mask {
+            payload.oidc.client_secret = config.oidc.client_secret.clone();
         }
+        
+        state.overrides.apply(&mut payload);
+        let changes = transfer::diff(&config, &payload);
+        *config = payload.clone();
+        changes
+    };
+    persist_config(&state, &payload).await.map_err(internal_err)?;
+    state.refresh.notify_one();
+    let (before, after) = transfer::audit_states(&changes);
+    let event = db::AuditEvent::new("System", "Config Updated", format!("Connection settings updated via Dashboard, {} change(s)", changes.len()))
+        .before(before)
+        .after(after);
+    audit_event(&state, Some(&user), event).await;
+    Ok(axum::http::StatusCode::OK)
+}
+
+/// Rejects link-local service URLs, malformed additional instances, incomplete OIDC settings
+/// and session lifetimes outside 1 hour to a year.
+fn validate_config(config: &Config) -> Result<(), AppError> {
+    if !auth::valid_session_lifetime(config.session_lifetime_hours) {
+        let message = format!("Session lifetime must be between 1 and {} hours", auth::MAX_SESSION_LIFETIME_HOURS);
+        return Err((axum::http::StatusCode::BAD_REQUEST, message));
+    }
+    let is_safe = |u: &str| !u.contains("169.254.");
+    let all_safe = std::iter::once(&config.services)
+        .chain(config.instances.iter().map(|instance| &instance.settings))
+        .all(|settings| api::all_fields().filter(|f| f.kind == api::FieldKind::Url).all(|f| is_safe(settings.get(f.key))));
+    if !all_safe {
+        return Err((axum::http::StatusCode::BAD_REQUEST, "Link-local URLs are not allowed".to_string()));
     }
+    api::validate_instances(&config.instances).map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+    config.oidc.validate().map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))
+}
 
-    Ok(Json(serde_json::json!({
-        "sonarr_disk": sonarr_disk,
-        "radarr_disk": radarr_disk,
-    })))
+#[derive(Deserialize)]
+struct ExportRequest {
+    #[serde(default = "default_export_format")]
+    format: transfer::Format,
+    /// Required, so secrets never leave in plaintext by accident.
+    secrets: transfer::SecretsMode,
+    passphrase: Option<String>,
 }
 
-async fn get_dashboard_config(
+fn default_export_format() -> transfer::Format {
+    transfer::Format::Json
+}
+
+async fn export_config(
     State(state): State<Arc<AppState>>,
-) -> Json<Config> {
-    let mut config = state.config.read().await.clone();
-    
-    let mask = "********".to_string();
-    if !config.dashboard_pass.is_empty() { config.dashboard_pass = mask.clone(); }
-    if !config.sonarr_key.is_empty() { config.sonarr_key = mask.clone(); }
-    if !config.radarr_key.is_empty() { config.radarr_key = mask.clone(); }
-    if !config.jackett_key.is_empty() { config.jackett_key = mask.clone(); }
-    if !config.transmission_pass.is_empty() { config.transmission_pass = mask.clone(); }
-    if !config.plex_token.is_empty() { config.plex_token = mask.clone(); }
-    if !config.jellyfin_key.is_empty() { config.jellyfin_key = mask.clone(); }
-    if !config.emby_key.is_empty() { config.emby_key = mask.clone(); }
-    if !config.prowlarr_key.is_empty() { config.prowlarr_key = mask.clone(); }
-    if !config.lidarr_key.is_empty() { config.lidarr_key = mask; }
-    
-    Json(config)
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Json(request): Json<ExportRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let document = {
+        let config = state.config.read().await;
+        transfer::export(&config, request.secrets, request.passphrase.as_deref())
+            .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?
+    };
+    let body = transfer::render(&document, request.format).map_err(internal_err)?;
+    let secrets = serde_json::to_value(request.secrets).ok().and_then(|v| v.as_str().map(String::from)).unwrap_or_default();
+    audit(&state, Some(&user), "System", "Config Exported", &format!("{} service(s), secrets {}", document.services.len(), secrets)).await;
+
+    let filename = format!(
+        "media-dashboard-config-{}.{}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+        request.format.extension()
+    );
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, request.format.content_type().to_string()),
+            (axum::http::header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    ))
 }
 
-async fn update_dashboard_config(
+#[derive(Deserialize)]
+struct ImportQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Header carrying the passphrase of an export with encrypted secrets.
+const PASSPHRASE_HEADER: &str = "x-config-passphrase";
+
+async fn import_config(
     State(state): State<Arc<AppState>>,
-    Json(mut payload): Json<Config>,
-) -> axum::http::StatusCode {
-    let is_safe = |u: &str| !u.contains("169.254.");
-    if !is_safe(&payload.sonarr_url) || !is_safe(&payload.radarr_url) || !is_safe(&payload.jackett_url) || !is_safe(&payload.transmission_url) || !is_safe(&payload.plex_url) || !is_safe(&payload.jellyfin_url) || !is_safe(&payload.emby_url) || !is_safe(&payload.prowlarr_url) || !is_safe(&payload.lidarr_url) {
-        return axum::http::StatusCode::BAD_REQUEST;
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Query(query): Query<ImportQuery>,
+    headers: axum::http::HeaderMap,
+    body: String,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let header_value = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let format = match header_value(axum::http::header::CONTENT_TYPE.as_str()) {
+        Some(content_type) if content_type.contains("yaml") => transfer::Format::Yaml,
+        _ => transfer::Format::Json,
+    };
+    let document = transfer::parse(&body, format).map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+
+    let mut config = state.config.write().await;
+    let mut next = transfer::import(document, &config, header_value(PASSPHRASE_HEADER))
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+    state.overrides.apply(&mut next);
+    validate_config(&next)?;
+    let changes = transfer::diff(&config, &next);
+    if query.dry_run || changes.is_empty() {
+        return Ok(Json(serde_json::json!({ "dry_run": query.dry_run, "applied": false, "changes": changes })));
     }
 
-    let mask = "********";
-    {
-        let mut config = state.config.write().await;
-        
-        // Preserve existing keys if incoming payload has the mask or is empty
-        if payload.dashboard_user.is_empty() { payload.dashboard_user = config.dashboard_user.clone(); }
-        if payload.dashboard_pass.is_empty() || payload.dashboard_pass == mask { payload.dashboard_pass = config.dashboard_pass.clone(); }
-        if payload.sonarr_key == mask { payload.sonarr_key = config.sonarr_key.clone(); }
-        if payload.radarr_key == mask { payload.radarr_key = config.radarr_key.clone(); }
-        if payload.jackett_key == mask { payload.jackett_key = config.jackett_key.clone(); }
-        if payload.transmission_pass == mask { payload.transmission_pass = config.transmission_pass.clone(); }
-        if payload.plex_token == mask { payload.plex_token = config.plex_token.clone(); }
-        if payload.jellyfin_key == mask { payload.jellyfin_key = config.jellyfin_key.clone(); }
-        if payload.emby_key == mask { payload.emby_key = config.emby_key.clone(); }
-        if payload.prowlarr_key == mask { payload.prowlarr_key = config.prowlarr_key.clone(); }
-        if payload.lidarr_key == mask { payload.lidarr_key = config.lidarr_key.clone(); }
-        
-        *config = payload.clone();
-    }
-    save_config_to_db(&state.db, &payload).await;
-    db::log_event(&state.db, "System", "Config Updated", "Connection settings updated via Dashboard").await;
-    axum::http::StatusCode::OK
+    persist_config(&state, &next).await.map_err(internal_err)?;
+    *config = next;
+    drop(config);
+    state.refresh.notify_one();
+    let (before, after) = transfer::audit_states(&changes);
+    let event = db::AuditEvent::new("System", "Config Imported", format!("{} change(s) applied from an import", changes.len()))
+        .before(before)
+        .after(after);
+    audit_event(&state, Some(&user), event).await;
+    Ok(Json(serde_json::json!({ "dry_run": false, "applied": true, "changes": changes })))
+}
+
+/// Connects to the instance with id `service`; a bare service id such as "sonarr" names its primary instance.
+fn connect_service(state: &AppState, config: &Config, service: &str) -> Result<Box<dyn api::ServiceClient>, AppError> {
+    config.all_instances()
+        .into_iter()
+        .find(|instance| instance.id == service)
+        .and_then(|instance| Some((instance.descriptor()?.connect)(&state.client, &instance.settings)))
+        .filter(|client| client.capabilities().contains(&api::Capability::Config))
+        .ok_or((axum::http::StatusCode::NOT_FOUND, "Service not found".to_string()))
 }
 
 async fn get_service_settings(
     State(state): State<Arc<AppState>>,
     Path(service): Path<String>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    let client = &state.client;
-    match service.as_str() {
-        "sonarr" => api::sonarr::get_config(client, &config.sonarr_url, &config.sonarr_key)
-            .await.map(Json).map_err(|e| internal_err(e)),
-        "radarr" => api::radarr::get_config(client, &config.radarr_url, &config.radarr_key)
-            .await.map(Json).map_err(|e| internal_err(e)),
-        "transmission" => api::transmission::get_config(client, &config.transmission_url, &config.transmission_user, &config.transmission_pass)
-            .await.map(Json).map_err(|e| internal_err(e)),
-        "prowlarr" => api::prowlarr::get_config(client, &config.prowlarr_url, &config.prowlarr_key)
-            .await.map(Json).map_err(|e| internal_err(e)),
-        "lidarr" => api::lidarr::get_config(client, &config.lidarr_url, &config.lidarr_key)
-            .await.map(Json).map_err(|e| internal_err(e)),
-        _ => Err((axum::http::StatusCode::NOT_FOUND, "Service not found".to_string())),
-    }
+    let client = {
+        let config = state.config.read().await;
+        connect_service(&state, &config, &service)?
+    };
+    client.get_config().await.map(Json).map_err(internal_err)
 }
 
 async fn update_service_settings(
     State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
     Path(service): Path<String>,
     Json(payload): Json<serde_json::Value>,
 ) -> Result<axum::http::StatusCode, AppError> {
-    let config = state.config.read().await;
-    let client = &state.client;
-    let res = match service.as_str() {
-        "sonarr" => api::sonarr::update_config(client, &config.sonarr_url, &config.sonarr_key, payload).await,
-        "radarr" => api::radarr::update_config(client, &config.radarr_url, &config.radarr_key, payload).await,
-        "transmission" => api::transmission::update_config(client, &config.transmission_url, &config.transmission_user, &config.transmission_pass, payload).await,
-        "prowlarr" => api::prowlarr::update_config(client, &config.prowlarr_url, &config.prowlarr_key, payload).await,
-        "lidarr" => api::lidarr::update_config(client, &config.lidarr_url, &config.lidarr_key, payload).await,
-        _ => return Err((axum::http::StatusCode::NOT_FOUND, "Service not found".to_string())),
+    let client = {
+        let config = state.config.read().await;
+        connect_service(&state, &config, &service)?
     };
-    if res.is_ok() {
-        db::log_event(&state.db, &service, "Settings Updated", "Configuration changes applied via Dashboard").await;
-        Ok(axum::http::StatusCode::OK)
-    } else {
-        Err(internal_err(res.err().unwrap()))
+    let event = db::AuditEvent::new(&service, "Settings Updated", format!("Configuration of {} changed via Dashboard", service))
+        .target(&service);
+    let attempt = format!("Failed to change the configuration of {}", service);
+    audit_result(&state, &user, event, &attempt, client.update_config(payload).await).await?;
+    Ok(axum::http::StatusCode::OK)
+}
+
+/// Filters and paging of the audit log and its export. Empty values are ignored, so a
+/// form can submit every field.
+#[derive(Deserialize)]
+struct AuditQuery {
+    service: Option<String>,
+    action: Option<String>,
+    user: Option<String>,
+    outcome: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    /// Words to find in the details or target title.
+    q: Option<String>,
+    /// The `next_cursor` of the previous page.
+    cursor: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl AuditQuery {
+    fn filter(&self) -> Result<db::AuditFilter, AppError> {
+        let given = |value: &Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(String::from);
+        let time = |name: &str, value: &Option<String>| {
+            given(value)
+                .map(|value| audit::parse_time(name, &value))
+                .transpose()
+                .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))
+        };
+        Ok(db::AuditFilter {
+            service: given(&self.service),
+            action: given(&self.action),
+            username: given(&self.user),
+            outcome: given(&self.outcome),
+            from: time("from", &self.from)?,
+            to: time("to", &self.to)?,
+            search: given(&self.q),
+        })
     }
 }
 
+/// One page of audit entries, newest first. `next_cursor` fetches the page after it and is
+/// `null` on the last one.
 async fn get_audit_logs(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let logs = sqlx::query_as::<_, db::AuditLog>("SELECT id, timestamp, service, action, details FROM audit_logs ORDER BY timestamp DESC LIMIT 100")
-        .fetch_all(&state.db)
+    let filter = query.filter()?;
+    let limit = query.limit.unwrap_or(audit::DEFAULT_PAGE_SIZE).clamp(1, audit::MAX_PAGE_SIZE);
+    let entries = db::query_audit_logs(&state.db, &filter, query.cursor, limit)
         .await
-        .map_err(|e| internal_err(e))?;
-    Ok(Json(serde_json::to_value(logs).unwrap()))
+        .map_err(internal_err)?;
+    let next_cursor = entries.last().filter(|_| entries.len() as i64 == limit).map(|last| last.id);
+    Ok(Json(serde_json::json!({ "entries": entries, "next_cursor": next_cursor })))
+}
+
+#[derive(Deserialize)]
+struct AuditExportQuery {
+    format: audit::Format,
+}
+
+/// Downloads every audit entry matching the filters as CSV or JSON lines.
+async fn export_audit_logs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+    Query(export): Query<AuditExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let filter = query.filter()?;
+    let filename = format!(
+        "media-dashboard-audit-{}.{}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+        export.format.extension()
+    );
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, export.format.content_type().to_string()),
+            (axum::http::header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        axum::body::Body::from_stream(audit::export(state.db.clone(), filter, export.format)),
+    ))
+}
+
+async fn get_login_logs(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<db::LoginEvent>>, AppError> {
+    let logins = db::recent_logins(&state.db, 100).await.map_err(internal_err)?;
+    Ok(Json(logins))
 }
 
 // ===================== Sonarr Handlers =====================
 
+/// Sonarr and Radarr answer a request they reject, such as adding a series that is already
+/// there, with a list of validation errors instead of the item.
+fn arr_rejection(response: &serde_json::Value) -> Option<String> {
+    let errors = response.as_array()?;
+    let messages: Vec<&str> = errors.iter().filter_map(|e| e["errorMessage"].as_str()).collect();
+    Some(if messages.is_empty() { response.to_string() } else { messages.join("; ") })
+}
+
+/// The fields of a Sonarr series or Radarr movie kept as the before or after state of an audit entry.
+fn media_state(item: &serde_json::Value) -> serde_json::Value {
+    pick_fields(item, &["title", "year", "tvdbId", "tmdbId", "qualityProfileId", "rootFolderPath", "path", "monitored"])
+}
+
+/// The `fields` of `item` that are set.
+fn pick_fields(item: &serde_json::Value, fields: &[&str]) -> serde_json::Value {
+    fields
+        .iter()
+        .filter(|field| !item[**field].is_null())
+        .map(|field| (field.to_string(), item[*field].clone()))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Names a target in audit details: `'Title'` when its title is known, `#id` otherwise.
+fn audit_name(title: Option<&str>, id: impl std::fmt::Display) -> String {
+    title.map_or_else(|| format!("#{}", id), |title| format!("'{}'", title))
+}
+
 async fn sonarr_list_series(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    api::sonarr::list_series(&state.client, &config.sonarr_url, &config.sonarr_key)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    api::sonarr::list_series(&state.client, instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"))
+        .await.map(Json).map_err(internal_err)
 }
 
 async fn sonarr_search_series(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
     Query(q): Query<SearchQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let term = q.term.unwrap_or_default();
     if term.is_empty() {
         return Err((axum::http::StatusCode::BAD_REQUEST, "Missing 'term' parameter".to_string()));
     }
-    let config = state.config.read().await;
-    api::sonarr::search_series(&state.client, &config.sonarr_url, &config.sonarr_key, &term)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    api::sonarr::search_series(&state.client, instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"), &term)
+        .await.map(Json).map_err(internal_err)
 }
 
 async fn sonarr_add_series(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
     Json(body): Json<serde_json::Value>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    let result = api::sonarr::add_series(&state.client, &config.sonarr_url, &config.sonarr_key, body)
-        .await.map_err(|e| internal_err(e))?;
-    db::log_event(&state.db, "Sonarr", "Series Added", "New series added via Dashboard").await;
-    Ok(Json(result))
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    let title = body["title"].as_str().unwrap_or("unknown").to_string();
+    let result = api::sonarr::add_series(&state.client, instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"), body.clone())
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|added| arr_rejection(&added).map_or(Ok(added), Err));
+    let mut event = db::AuditEvent::new(instance.label_or("Sonarr"), "Series Added", format!("Series '{}' added via Dashboard", title))
+        .title(&title)
+        .after(media_state(result.as_ref().unwrap_or(&body)));
+    if let Some(id) = result.as_ref().ok().and_then(|added| added["id"].as_i64()) {
+        event = event.target(id);
+    }
+    let attempt = format!("Failed to add series '{}'", title);
+    audit_result(&state, &user, event, &attempt, result).await.map(Json)
 }
 
 async fn sonarr_delete_series(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
     Path(id): Path<i64>,
     Query(q): Query<DeleteQuery>,
 ) -> Result<axum::http::StatusCode, AppError> {
-    let config = state.config.read().await;
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
     let delete_files = q.delete_files.unwrap_or(false);
-    api::sonarr::delete_series(&state.client, &config.sonarr_url, &config.sonarr_key, id, delete_files)
-        .await.map_err(|e| internal_err(e))?;
-    db::log_event(&state.db, "Sonarr", "Series Deleted", &format!("Series {} removed via Dashboard", id)).await;
+    let (url, api_key) = (instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"));
+    let series = api::sonarr::get_series(&state.client, url, api_key, id).await.ok();
+    let title = series.as_ref().and_then(|series| series["title"].as_str());
+    let files = if delete_files { " with its files" } else { "" };
+    let mut event = db::AuditEvent::new(instance.label_or("Sonarr"), "Series Deleted", format!("Series {} removed{} via Dashboard", audit_name(title, id), files))
+        .target(id);
+    if let Some(series) = &series {
+        event = event.title(title.unwrap_or_default()).before(media_state(series));
+    }
+    let attempt = format!("Failed to remove series {}", audit_name(title, id));
+    audit_result(&state, &user, event, &attempt, api::sonarr::delete_series(&state.client, url, api_key, id, delete_files).await).await?;
     Ok(axum::http::StatusCode::OK)
 }
 
 async fn sonarr_root_folders(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    api::sonarr::get_root_folders(&state.client, &config.sonarr_url, &config.sonarr_key)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    api::sonarr::get_root_folders(&state.client, instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"))
+        .await.map(Json).map_err(internal_err)
 }
 
 async fn sonarr_quality_profiles(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    api::sonarr::get_quality_profiles(&state.client, &config.sonarr_url, &config.sonarr_key)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    api::sonarr::get_quality_profiles(&state.client, instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"))
+        .await.map(Json).map_err(internal_err)
+}
+
+#[derive(Deserialize)]
+struct SeasonQuery {
+    season: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct MonitorRequest {
+    monitored: bool,
+}
+
+/// The seasons of a series with whether each is monitored and its episode counts.
+async fn sonarr_list_seasons(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    let series = api::sonarr::get_series(&state.client, instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"), id)
+        .await
+        .map_err(internal_err)?;
+    Ok(Json(series["seasons"].clone()))
+}
+
+/// The episodes of a series, or of one season with `?season=`.
+async fn sonarr_list_episodes(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    Query(query): Query<SeasonQuery>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    api::sonarr::list_episodes(&state.client, instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"), id, query.season)
+        .await.map(Json).map_err(internal_err)
+}
+
+async fn sonarr_episode_files(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    api::sonarr::list_episode_files(&state.client, instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"), id)
+        .await.map(Json).map_err(internal_err)
+}
+
+/// Episode `episode_id`, provided it belongs to series `series_id`.
+async fn sonarr_episode(state: &AppState, url: &str, api_key: &str, series_id: i64, episode_id: i64) -> Result<serde_json::Value, AppError> {
+    let episode = api::sonarr::get_episode(&state.client, url, api_key, episode_id).await.map_err(internal_err)?;
+    if episode["seriesId"].as_i64() != Some(series_id) {
+        return Err((axum::http::StatusCode::NOT_FOUND, format!("Episode {} is not part of series {}", episode_id, series_id)));
+    }
+    Ok(episode)
+}
+
+/// Names an episode in audit details, e.g. `'Severance' S01E02`.
+fn episode_name(episode: &serde_json::Value) -> String {
+    let code = format!(
+        "S{:02}E{:02}",
+        episode["seasonNumber"].as_i64().unwrap_or_default(),
+        episode["episodeNumber"].as_i64().unwrap_or_default()
+    );
+    match episode["series"]["title"].as_str() {
+        Some(series) => format!("'{}' {}", series, code),
+        None => code,
+    }
+}
+
+async fn sonarr_monitor_season(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path((id, season)): Path<(i64, i64)>,
+    Json(request): Json<MonitorRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    let (url, api_key) = (instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"));
+    let mut series = api::sonarr::get_series(&state.client, url, api_key, id).await.map_err(internal_err)?;
+    let title = series["title"].as_str().map(String::from);
+    let name = audit_name(title.as_deref(), id);
+    let entry = series["seasons"]
+        .as_array_mut()
+        .and_then(|seasons| seasons.iter_mut().find(|s| s["seasonNumber"].as_i64() == Some(season)))
+        .ok_or_else(|| (axum::http::StatusCode::NOT_FOUND, format!("Series {} has no season {}", name, season)))?;
+    let before = serde_json::json!({ "season": season, "monitored": entry["monitored"] });
+    entry["monitored"] = request.monitored.into();
+
+    let (action, change) = if request.monitored { ("Season Monitored", "monitored") } else { ("Season Unmonitored", "unmonitored") };
+    let event = db::AuditEvent::new(instance.label_or("Sonarr"), action, format!("Season {} of {} {} via Dashboard", season, name, change))
+        .target(id)
+        .title(title.as_deref().unwrap_or_default())
+        .before(before)
+        .after(serde_json::json!({ "season": season, "monitored": request.monitored }));
+    let attempt = format!("Failed to change monitoring of season {} of {}", season, name);
+    audit_result(&state, &user, event, &attempt, api::sonarr::update_series(&state.client, url, api_key, id, &series).await).await.map(Json)
+}
+
+async fn sonarr_monitor_episode(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path((id, episode_id)): Path<(i64, i64)>,
+    Json(request): Json<MonitorRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    let (url, api_key) = (instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"));
+    let mut episode = sonarr_episode(&state, url, api_key, id, episode_id).await?;
+    let name = episode_name(&episode);
+    let before = serde_json::json!({ "monitored": episode["monitored"] });
+    episode["monitored"] = request.monitored.into();
+
+    let (action, change) = if request.monitored { ("Episode Monitored", "monitored") } else { ("Episode Unmonitored", "unmonitored") };
+    let event = db::AuditEvent::new(instance.label_or("Sonarr"), action, format!("Episode {} {} via Dashboard", name, change))
+        .target(episode_id)
+        .title(&name)
+        .before(before)
+        .after(serde_json::json!({ "monitored": request.monitored }));
+    let attempt = format!("Failed to change monitoring of episode {}", name);
+    let result = api::sonarr::set_episodes_monitored(&state.client, url, api_key, &[episode_id], request.monitored).await;
+    audit_result(&state, &user, event, &attempt, result).await?;
+    Ok(Json(episode))
+}
+
+/// Starts a search for every monitored episode of a series that is missing or below its cutoff.
+async fn sonarr_series_search(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    let (url, api_key) = (instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"));
+    let series = api::sonarr::get_series(&state.client, url, api_key, id).await.ok();
+    let title = series.as_ref().and_then(|series| series["title"].as_str());
+    let name = audit_name(title, id);
+    let event = db::AuditEvent::new(instance.label_or("Sonarr"), "Series Search", format!("Search for series {} started via Dashboard", name))
+        .target(id)
+        .title(title.unwrap_or_default());
+    let command = serde_json::json!({ "name": "SeriesSearch", "seriesId": id });
+    let attempt = format!("Failed to search for series {}", name);
+    audit_result(&state, &user, event, &attempt, api::sonarr::run_command(&state.client, url, api_key, &command).await).await.map(Json)
+}
+
+async fn sonarr_season_search(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path((id, season)): Path<(i64, i64)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    let (url, api_key) = (instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"));
+    let series = api::sonarr::get_series(&state.client, url, api_key, id).await.ok();
+    let title = series.as_ref().and_then(|series| series["title"].as_str());
+    let name = audit_name(title, id);
+    let event = db::AuditEvent::new(instance.label_or("Sonarr"), "Season Search", format!("Search for season {} of {} started via Dashboard", season, name))
+        .target(id)
+        .title(title.unwrap_or_default());
+    let command = serde_json::json!({ "name": "SeasonSearch", "seriesId": id, "seasonNumber": season });
+    let attempt = format!("Failed to search for season {} of {}", season, name);
+    audit_result(&state, &user, event, &attempt, api::sonarr::run_command(&state.client, url, api_key, &command).await).await.map(Json)
+}
+
+async fn sonarr_episode_search(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path((id, episode_id)): Path<(i64, i64)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "sonarr", target.instance.as_deref()).await?;
+    let (url, api_key) = (instance.settings.get("sonarr_url"), instance.settings.get("sonarr_key"));
+    let episode = sonarr_episode(&state, url, api_key, id, episode_id).await?;
+    let name = episode_name(&episode);
+    let event = db::AuditEvent::new(instance.label_or("Sonarr"), "Episode Search", format!("Search for episode {} started via Dashboard", name))
+        .target(episode_id)
+        .title(&name);
+    let command = serde_json::json!({ "name": "EpisodeSearch", "episodeIds": [episode_id] });
+    let attempt = format!("Failed to search for episode {}", name);
+    audit_result(&state, &user, event, &attempt, api::sonarr::run_command(&state.client, url, api_key, &command).await).await.map(Json)
 }
 
 // ===================== Radarr Handlers =====================
 
 async fn radarr_list_movies(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    api::radarr::list_movies(&state.client, &config.radarr_url, &config.radarr_key)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    api::radarr::list_movies(&state.client, instance.settings.get("radarr_url"), instance.settings.get("radarr_key"))
+        .await.map(Json).map_err(internal_err)
 }
 
 async fn radarr_search_movies(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
     Query(q): Query<SearchQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let term = q.term.unwrap_or_default();
     if term.is_empty() {
         return Err((axum::http::StatusCode::BAD_REQUEST, "Missing 'term' parameter".to_string()));
     }
-    let config = state.config.read().await;
-    api::radarr::search_movies(&state.client, &config.radarr_url, &config.radarr_key, &term)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    api::radarr::search_movies(&state.client, instance.settings.get("radarr_url"), instance.settings.get("radarr_key"), &term)
+        .await.map(Json).map_err(internal_err)
 }
 
 async fn radarr_add_movie(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
     Json(body): Json<serde_json::Value>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    let result = api::radarr::add_movie(&state.client, &config.radarr_url, &config.radarr_key, body)
-        .await.map_err(|e| internal_err(e))?;
-    db::log_event(&state.db, "Radarr", "Movie Added", "New movie added via Dashboard").await;
-    Ok(Json(result))
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    let title = body["title"].as_str().unwrap_or("unknown").to_string();
+    let result = api::radarr::add_movie(&state.client, instance.settings.get("radarr_url"), instance.settings.get("radarr_key"), body.clone())
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|added| arr_rejection(&added).map_or(Ok(added), Err));
+    let mut event = db::AuditEvent::new(instance.label_or("Radarr"), "Movie Added", format!("Movie '{}' added via Dashboard", title))
+        .title(&title)
+        .after(media_state(result.as_ref().unwrap_or(&body)));
+    if let Some(id) = result.as_ref().ok().and_then(|added| added["id"].as_i64()) {
+        event = event.target(id);
+    }
+    let attempt = format!("Failed to add movie '{}'", title);
+    audit_result(&state, &user, event, &attempt, result).await.map(Json)
 }
 
 async fn radarr_delete_movie(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
     Path(id): Path<i64>,
     Query(q): Query<DeleteQuery>,
 ) -> Result<axum::http::StatusCode, AppError> {
-    let config = state.config.read().await;
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
     let delete_files = q.delete_files.unwrap_or(false);
-    api::radarr::delete_movie(&state.client, &config.radarr_url, &config.radarr_key, id, delete_files)
-        .await.map_err(|e| internal_err(e))?;
-    db::log_event(&state.db, "Radarr", "Movie Deleted", &format!("Movie {} removed via Dashboard", id)).await;
+    let (url, api_key) = (instance.settings.get("radarr_url"), instance.settings.get("radarr_key"));
+    let movie = api::radarr::get_movie(&state.client, url, api_key, id).await.ok();
+    let title = movie.as_ref().and_then(|movie| movie["title"].as_str());
+    let files = if delete_files { " with its files" } else { "" };
+    let mut event = db::AuditEvent::new(instance.label_or("Radarr"), "Movie Deleted", format!("Movie {} removed{} via Dashboard", audit_name(title, id), files))
+        .target(id);
+    if let Some(movie) = &movie {
+        event = event.title(title.unwrap_or_default()).before(media_state(movie));
+    }
+    let attempt = format!("Failed to remove movie {}", audit_name(title, id));
+    audit_result(&state, &user, event, &attempt, api::radarr::delete_movie(&state.client, url, api_key, id, delete_files).await).await?;
     Ok(axum::http::StatusCode::OK)
 }
 
+/// Changes to a Radarr movie; fields left out stay as they are.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MovieEdit {
+    quality_profile_id: Option<i64>,
+    monitored: Option<bool>,
+    /// Puts the movie's folder under this root folder, keeping its name.
+    root_folder_path: Option<String>,
+    /// Move the files to the new root folder, rather than only pointing Radarr at it.
+    #[serde(default)]
+    move_files: bool,
+    minimum_availability: Option<String>,
+    /// Tag ids, replacing the movie's tags.
+    tags: Option<Vec<i64>>,
+}
+
+/// Radarr's availability values, including "tba" for movies it has no date for yet.
+const MINIMUM_AVAILABILITY: [&str; 4] = ["tba", "announced", "inCinemas", "released"];
+/// The settings `radarr_edit_movie` may change, kept as the before and after state of its audit entry.
+const MOVIE_EDIT_FIELDS: [&str; 6] = ["qualityProfileId", "monitored", "rootFolderPath", "path", "minimumAvailability", "tags"];
+
+async fn radarr_edit_movie(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path(id): Path<i64>,
+    Json(edit): Json<MovieEdit>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if let Some(availability) = edit.minimum_availability.as_deref() {
+        if !MINIMUM_AVAILABILITY.contains(&availability) {
+            let allowed = MINIMUM_AVAILABILITY.join(", ");
+            return Err((axum::http::StatusCode::BAD_REQUEST, format!("minimumAvailability: '{}' is not one of {}", availability, allowed)));
+        }
+    }
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    let (url, api_key) = (instance.settings.get("radarr_url"), instance.settings.get("radarr_key"));
+    let mut movie = api::radarr::get_movie(&state.client, url, api_key, id).await.map_err(internal_err)?;
+    let title = movie["title"].as_str().map(String::from);
+    let name = audit_name(title.as_deref(), id);
+    let before = pick_fields(&movie, &MOVIE_EDIT_FIELDS);
+
+    if let Some(profile) = edit.quality_profile_id {
+        movie["qualityProfileId"] = profile.into();
+    }
+    if let Some(monitored) = edit.monitored {
+        movie["monitored"] = monitored.into();
+    }
+    if let Some(availability) = edit.minimum_availability.as_deref() {
+        movie["minimumAvailability"] = availability.into();
+    }
+    if let Some(tags) = &edit.tags {
+        movie["tags"] = tags.clone().into();
+    }
+    if let Some(root) = edit.root_folder_path.as_deref() {
+        // Older Radarr versions have no `folder`, only the full path
+        let folder = movie["folder"]
+            .as_str()
+            .or_else(|| movie["path"].as_str()?.rsplit(['/', '\\']).find(|part| !part.is_empty()))
+            .map(String::from)
+            .ok_or_else(|| (axum::http::StatusCode::CONFLICT, format!("Movie {} has no folder to move", name)))?;
+        let separator = if root.contains('\\') && !root.contains('/') { '\\' } else { '/' };
+        movie["path"] = format!("{}{}{}", root.trim_end_matches(['/', '\\']), separator, folder).into();
+        movie["rootFolderPath"] = root.into();
+    }
+
+    let result = api::radarr::update_movie(&state.client, url, api_key, id, &movie, edit.move_files)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|updated| arr_rejection(&updated).map_or(Ok(updated), Err));
+    let after = pick_fields(result.as_ref().unwrap_or(&movie), &MOVIE_EDIT_FIELDS);
+    let changed: Vec<&str> = MOVIE_EDIT_FIELDS.iter().copied().filter(|field| before[*field] != after[*field]).collect();
+    let moved = if edit.move_files && edit.root_folder_path.is_some() { ", files moved" } else { "" };
+    let changes = if changed.is_empty() { "no changes".to_string() } else { changed.join(", ") };
+    let event = db::AuditEvent::new(instance.label_or("Radarr"), "Movie Edited", format!("Movie {} edited via Dashboard ({}{})", name, changes, moved))
+        .target(id)
+        .title(title.as_deref().unwrap_or_default())
+        .before(before)
+        .after(after);
+    let attempt = format!("Failed to edit movie {}", name);
+    audit_result(&state, &user, event, &attempt, result).await.map(Json)
+}
+
+/// Starts a search for the movie, if it is monitored and missing or below its cutoff.
+async fn radarr_movie_search(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    let (url, api_key) = (instance.settings.get("radarr_url"), instance.settings.get("radarr_key"));
+    let movie = api::radarr::get_movie(&state.client, url, api_key, id).await.ok();
+    let title = movie.as_ref().and_then(|movie| movie["title"].as_str());
+    let name = audit_name(title, id);
+    let event = db::AuditEvent::new(instance.label_or("Radarr"), "Movie Search", format!("Search for movie {} started via Dashboard", name))
+        .target(id)
+        .title(title.unwrap_or_default());
+    let command = serde_json::json!({ "name": "MoviesSearch", "movieIds": [id] });
+    let attempt = format!("Failed to search for movie {}", name);
+    audit_result(&state, &user, event, &attempt, api::radarr::run_command(&state.client, url, api_key, &command).await).await.map(Json)
+}
+
+/// Rescans the movie's folder and refreshes its metadata.
+async fn radarr_refresh_movie(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    let (url, api_key) = (instance.settings.get("radarr_url"), instance.settings.get("radarr_key"));
+    let movie = api::radarr::get_movie(&state.client, url, api_key, id).await.ok();
+    let title = movie.as_ref().and_then(|movie| movie["title"].as_str());
+    let name = audit_name(title, id);
+    let event = db::AuditEvent::new(instance.label_or("Radarr"), "Movie Refresh", format!("Refresh of movie {} started via Dashboard", name))
+        .target(id)
+        .title(title.unwrap_or_default());
+    let command = serde_json::json!({ "name": "RefreshMovie", "movieIds": [id] });
+    let attempt = format!("Failed to refresh movie {}", name);
+    audit_result(&state, &user, event, &attempt, api::radarr::run_command(&state.client, url, api_key, &command).await).await.map(Json)
+}
+
+async fn radarr_movie_files(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    api::radarr::list_movie_files(&state.client, instance.settings.get("radarr_url"), instance.settings.get("radarr_key"), id)
+        .await.map(Json).map_err(internal_err)
+}
+
+async fn radarr_movie_history(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    api::radarr::movie_history(&state.client, instance.settings.get("radarr_url"), instance.settings.get("radarr_key"), id)
+        .await.map(Json).map_err(internal_err)
+}
+
 async fn radarr_root_folders(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    api::radarr::get_root_folders(&state.client, &config.radarr_url, &config.radarr_key)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    api::radarr::get_root_folders(&state.client, instance.settings.get("radarr_url"), instance.settings.get("radarr_key"))
+        .await.map(Json).map_err(internal_err)
 }
 
 async fn radarr_quality_profiles(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    api::radarr::get_quality_profiles(&state.client, &config.radarr_url, &config.radarr_key)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "radarr", target.instance.as_deref()).await?;
+    api::radarr::get_quality_profiles(&state.client, instance.settings.get("radarr_url"), instance.settings.get("radarr_key"))
+        .await.map(Json).map_err(internal_err)
 }
 
 // ===================== Jackett Handlers =====================
@@ -669,8 +2348,8 @@ async fn jackett_list_indexers(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let config = state.config.read().await;
-    api::jackett::list_indexers(&state.client, &config.jackett_url, &config.jackett_key)
-        .await.map(Json).map_err(|e| internal_err(e))
+    api::jackett::list_indexers(&state.client, config.services.get("jackett_url"), config.services.get("jackett_key"))
+        .await.map(Json).map_err(internal_err)
 }
 
 // ===================== Plex Handlers =====================
@@ -679,8 +2358,8 @@ async fn plex_get_libraries(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let config = state.config.read().await;
-    let libs = api::plex::get_libraries(&state.client, &config.plex_url, &config.plex_token)
-        .await.map_err(|e| internal_err(e))?;
+    let libs = api::plex::get_libraries(&state.client, config.services.get("plex_url"), config.services.get("plex_token"))
+        .await.map_err(internal_err)?;
     Ok(Json(serde_json::to_value(libs).unwrap_or_default()))
 }
 
@@ -688,8 +2367,8 @@ async fn plex_recently_added(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let config = state.config.read().await;
-    let items = api::plex::get_recently_added(&state.client, &config.plex_url, &config.plex_token, 30)
-        .await.map_err(|e| internal_err(e))?;
+    let items = api::plex::get_recently_added(&state.client, config.services.get("plex_url"), config.services.get("plex_token"), 30)
+        .await.map_err(internal_err)?;
     Ok(Json(serde_json::to_value(items).unwrap_or_default()))
 }
 
@@ -697,81 +2376,151 @@ async fn plex_server_info(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let config = state.config.read().await;
-    api::plex::get_server_info(&state.client, &config.plex_url, &config.plex_token)
-        .await.map(Json).map_err(|e| internal_err(e))
+    api::plex::get_server_info(&state.client, config.services.get("plex_url"), config.services.get("plex_token"))
+        .await.map(Json).map_err(internal_err)
 }
 
 // ===================== Prowlarr Handlers =====================
 
 async fn prowlarr_list_indexers(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    api::prowlarr::list_indexers(&state.client, &config.prowlarr_url, &config.prowlarr_key)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "prowlarr", target.instance.as_deref()).await?;
+    api::prowlarr::list_indexers(&state.client, instance.settings.get("prowlarr_url"), instance.settings.get("prowlarr_key"))
+        .await.map(Json).map_err(internal_err)
 }
 
 async fn prowlarr_get_status(
     State(state): State<Arc<AppState>>,
-) -> Json<ServiceStatus> {
-    let config = state.config.read().await;
-    Json(api::prowlarr::get_status(&state.client, &config.prowlarr_url, &config.prowlarr_key).await)
+    Query(target): Query<InstanceQuery>,
+) -> Result<Json<ServiceStatus>, AppError> {
+    let instance = service_instance(&state, "prowlarr", target.instance.as_deref()).await?;
+    Ok(Json(cached_or_live_status(&state, &api::prowlarr::SERVICE, &instance).await))
+}
+
+/// Serves an instance's status from the poller snapshot, probing live only if it is not in there yet.
+async fn cached_or_live_status(state: &AppState, descriptor: &api::ServiceDescriptor, instance: &api::ServiceInstance) -> ServiceStatus {
+    let client = (descriptor.connect)(&state.client, &instance.settings);
+    let cached = state.snapshot.read().await
+        .statuses
+        .iter()
+        .find(|s| s.id == instance.id && s.url == client.url())
+        .cloned();
+    match cached {
+        Some(status) => status,
+        None => {
+            let mut status = client.status().await;
+            status.id = instance.id.clone();
+            status.name = instance.label_or(&status.name).to_string();
+            status
+        }
+    }
 }
 
 // ===================== Lidarr Handlers =====================
 
 async fn lidarr_list_artists(
     State(state): State<Arc<AppState>>,
+    Query(target): Query<InstanceQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let config = state.config.read().await;
-    api::lidarr::list_artists(&state.client, &config.lidarr_url, &config.lidarr_key)
-        .await.map(Json).map_err(|e| internal_err(e))
+    let instance = service_instance(&state, "lidarr", target.instance.as_deref()).await?;
+    api::lidarr::list_artists(&state.client, instance.settings.get("lidarr_url"), instance.settings.get("lidarr_key"))
+        .await.map(Json).map_err(internal_err)
 }
 
 async fn lidarr_get_status(
     State(state): State<Arc<AppState>>,
-) -> Json<ServiceStatus> {
-    let config = state.config.read().await;
-    Json(api::lidarr::get_status(&state.client, &config.lidarr_url, &config.lidarr_key).await)
+    Query(target): Query<InstanceQuery>,
+) -> Result<Json<ServiceStatus>, AppError> {
+    let instance = service_instance(&state, "lidarr", target.instance.as_deref()).await?;
+    Ok(Json(cached_or_live_status(&state, &api::lidarr::SERVICE, &instance).await))
 }
 
 // ===================== Transmission Handlers =====================
 
+/// Transmission reports a failed call in the `result` field of an otherwise successful response.
+fn rpc_outcome(response: Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>>) -> Result<serde_json::Value, String> {
+    let response = response.map_err(|e| e.to_string())?;
+    match response["result"].as_str() {
+        Some(result) if result != "success" => Err(result.to_string()),
+        _ => Ok(response),
+    }
+}
+
+/// The torrent with `id` as Transmission reports it, to name it in the audit log.
+async fn torrent_info(state: &AppState, config: &Config, id: i64) -> Option<serde_json::Value> {
+    let response = api::transmission::get_torrent(&state.client, config.services.get("transmission_url"), config.services.get("transmission_user"), config.services.get("transmission_pass"), id)
+        .await
+        .ok()?;
+    response["arguments"]["torrents"].get(0).cloned()
+}
+
+/// An audit entry about the torrent with `id`, with its name and progress when Transmission knows them.
+fn torrent_event(action: &str, details: String, id: i64, torrent: Option<&serde_json::Value>) -> db::AuditEvent {
+    let event = db::AuditEvent::new("Transmission", action, details).target(id);
+    match torrent {
+        Some(torrent) => event
+            .title(torrent["name"].as_str().unwrap_or_default())
+            .before(serde_json::json!({ "status": torrent["status"], "percentDone": torrent["percentDone"], "sizeWhenDone": torrent["sizeWhenDone"] })),
+        None => event,
+    }
+}
+
 async fn transmission_list_torrents(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let config = state.config.read().await;
-    api::transmission::list_torrents(&state.client, &config.transmission_url, &config.transmission_user, &config.transmission_pass)
-        .await.map(Json).map_err(|e| internal_err(e))
+    api::transmission::list_torrents(&state.client, config.services.get("transmission_url"), config.services.get("transmission_user"), config.services.get("transmission_pass"))
+        .await.map(Json).map_err(internal_err)
 }
 
 async fn transmission_add_torrent(
     State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
     Json(payload): Json<TorrentAddPayload>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let config = state.config.read().await;
-    let result = api::transmission::add_torrent(&state.client, &config.transmission_url, &config.transmission_user, &config.transmission_pass, &payload.filename)
-        .await.map_err(|e| internal_err(e))?;
-    db::log_event(&state.db, "Transmission", "Torrent Added", "New torrent added via Dashboard").await;
-    Ok(Json(result))
+    let result = rpc_outcome(api::transmission::add_torrent(&state.client, config.services.get("transmission_url"), config.services.get("transmission_user"), config.services.get("transmission_pass"), &payload.filename).await);
+    // The link itself is left out of the log: private tracker links carry a passkey
+    let mut event = db::AuditEvent::new("Transmission", "Torrent Added", "Torrent added via Dashboard");
+    if let Ok(response) = &result {
+        let (added, duplicate) = (&response["arguments"]["torrent-added"], &response["arguments"]["torrent-duplicate"]);
+        let torrent = if added.is_object() { added } else { duplicate };
+        let name = torrent["name"].as_str().unwrap_or("unknown");
+        let details = if duplicate.is_object() {
+            format!("Torrent '{}' was already in Transmission", name)
+        } else {
+            format!("Torrent '{}' added via Dashboard", name)
+        };
+        event = db::AuditEvent::new("Transmission", "Torrent Added", details).title(name);
+        if let Some(id) = torrent["id"].as_i64() {
+            event = event.target(id);
+        }
+    }
+    audit_result(&state, &user, event, "Failed to add a torrent", result).await.map(Json)
 }
 
 async fn transmission_remove_torrent(
     State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
     Path(id): Path<i64>,
     Query(q): Query<DeleteQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let config = state.config.read().await;
     let delete_data = q.delete_data.unwrap_or(false);
-    let result = api::transmission::remove_torrent(&state.client, &config.transmission_url, &config.transmission_user, &config.transmission_pass, id, delete_data)
-        .await.map_err(|e| internal_err(e))?;
-    db::log_event(&state.db, "Transmission", "Torrent Removed", &format!("Torrent {} removed via Dashboard", id)).await;
-    Ok(Json(result))
+    let torrent = torrent_info(&state, &config, id).await;
+    let data = if delete_data { " with its data" } else { "" };
+    let name = audit_name(torrent.as_ref().and_then(|t| t["name"].as_str()), id);
+    let event = torrent_event("Torrent Removed", format!("Torrent {} removed{} via Dashboard", name, data), id, torrent.as_ref());
+    let attempt = format!("Failed to remove torrent {}", name);
+    let result = rpc_outcome(api::transmission::remove_torrent(&state.client, config.services.get("transmission_url"), config.services.get("transmission_user"), config.services.get("transmission_pass"), id, delete_data).await);
+    audit_result(&state, &user, event, &attempt, result).await.map(Json)
 }
 
 // ===================== Config Helpers =====================
 
-async fn migrate_config_if_needed(pool: &SqlitePool) {
+async fn migrate_config_if_needed(pool: &SqlitePool, key: &secrets::SecretKey) {
     let path = "config.json";
     if let Ok(metadata) = fs::metadata(path) {
         if metadata.is_dir() {
@@ -781,92 +2530,341 @@ async fn migrate_config_if_needed(pool: &SqlitePool) {
     }
 
     if let Ok(data) = fs::read_to_string(path) {
-        if let Ok(config) = serde_json::from_str::<Config>(&data) {
+        if let Ok(legacy) = serde_json::from_str::<SetupPayload>(&data) {
             println!("Migrating config.json to database...");
-            save_config_to_db(pool, &config).await;
-            db::log_event(pool, "System", "Migration", "Successfully migrated config.json to database").await;
+            if let Err(e) = save_config_to_db(pool, key, &legacy.config).await {
+                tracing::error!("Failed to migrate config.json: {}", e);
+                return;
+            }
+            // Picked up by `migrate_dashboard_account` along with accounts stored in the database
+            db::set_setting(pool, "dashboard_user", &legacy.dashboard_user).await;
+            db::set_setting(pool, "dashboard_pass", &legacy.dashboard_pass).await;
+            db::log_event(pool, None, "System", "Migration", "Successfully migrated config.json to database").await;
             let _ = fs::rename("config.json", "config.json.bak");
         }
     }
 }
 
-async fn load_config_from_db(pool: &SqlitePool) -> Config {
-    Config {
-        dashboard_user: db::get_setting(pool, "dashboard_user").await.unwrap_or_default(),
-        dashboard_pass: db::get_setting(pool, "dashboard_pass").await.unwrap_or_default(),
-        sonarr_url: db::get_setting(pool, "sonarr_url").await.unwrap_or_default(),
-        sonarr_key: db::get_setting(pool, "sonarr_key").await.unwrap_or_default(),
-        radarr_url: db::get_setting(pool, "radarr_url").await.unwrap_or_default(),
-        radarr_key: db::get_setting(pool, "radarr_key").await.unwrap_or_default(),
-        jackett_url: db::get_setting(pool, "jackett_url").await.unwrap_or_default(),
-        jackett_key: db::get_setting(pool, "jackett_key").await.unwrap_or_default(),
-        transmission_url: db::get_setting(pool, "transmission_url").await.unwrap_or_default(),
-        transmission_user: db::get_setting(pool, "transmission_user").await.unwrap_or_default(),
-        transmission_pass: db::get_setting(pool, "transmission_pass").await.unwrap_or_default(),
-        plex_url: db::get_setting(pool, "plex_url").await.unwrap_or_default(),
-        plex_token: db::get_setting(pool, "plex_token").await.unwrap_or_default(),
-        jellyfin_url: db::get_setting(pool, "jellyfin_url").await.unwrap_or_default(),
-        jellyfin_key: db::get_setting(pool, "jellyfin_key").await.unwrap_or_default(),
-        emby_url: db::get_setting(pool, "emby_url").await.unwrap_or_default(),
-        emby_key: db::get_setting(pool, "emby_key").await.unwrap_or_default(),
-        prowlarr_url: db::get_setting(pool, "prowlarr_url").await.unwrap_or_default(),
-        prowlarr_key: db::get_setting(pool, "prowlarr_key").await.unwrap_or_default(),
-        lidarr_url: db::get_setting(pool, "lidarr_url").await.unwrap_or_default(),
-        lidarr_key: db::get_setting(pool, "lidarr_key").await.unwrap_or_default(),
-    }
-}
-
-async fn save_config_to_db(pool: &SqlitePool, config: &Config) {
-    db::set_setting(pool, "dashboard_user", &config.dashboard_user).await;
-    db::set_setting(pool, "dashboard_pass", &config.dashboard_pass).await;
-    db::set_setting(pool, "sonarr_url", &config.sonarr_url).await;
-    db::set_setting(pool, "sonarr_key", &config.sonarr_key).await;
-    db::set_setting(pool, "radarr_url", &config.radarr_url).await;
-    db::set_setting(pool, "radarr_key", &config.radarr_key).await;
-    db::set_setting(pool, "jackett_url", &config.jackett_url).await;
-    db::set_setting(pool, "jackett_key", &config.jackett_key).await;
-    db::set_setting(pool, "transmission_url", &config.transmission_url).await;
-    db::set_setting(pool, "transmission_user", &config.transmission_user).await;
-    db::set_setting(pool, "transmission_pass", &config.transmission_pass).await;
-    db::set_setting(pool, "plex_url", &config.plex_url).await;
-    db::set_setting(pool, "plex_token", &config.plex_token).await;
-    db::set_setting(pool, "jellyfin_url", &config.jellyfin_url).await;
-    db::set_setting(pool, "jellyfin_key", &config.jellyfin_key).await;
-    db::set_setting(pool, "emby_url", &config.emby_url).await;
-    db::set_setting(pool, "emby_key", &config.emby_key).await;
-    db::set_setting(pool, "prowlarr_url", &config.prowlarr_url).await;
-    db::set_setting(pool, "prowlarr_key", &config.prowlarr_key).await;
-    db::set_setting(pool, "lidarr_url", &config.lidarr_url).await;
-    db::set_setting(pool, "lidarr_key", &config.lidarr_key).await;
+/// Moves the single `dashboard_user`/`dashboard_pass` account of older versions into the
+/// `users` table as an admin. A plaintext password is hashed on its first successful login.
+async fn migrate_dashboard_account(pool: &SqlitePool) {
+    let user = db::get_setting(pool, "dashboard_user").await.unwrap_or_default();
+    let pass = db::get_setting(pool, "dashboard_pass").await.unwrap_or_default();
+    if user.is_empty() || pass.is_empty() {
+        return;
+    }
+    if db::create_first_user(pool, &user, &pass).await {
+        db::log_event(pool, None, "System", "Migration", &format!("Dashboard account '{}' migrated to an admin user", user)).await;
+    }
+    db::delete_setting(pool, "dashboard_user").await;
+    db::delete_setting(pool, "dashboard_pass").await;
+}
+
+/// Creates the first admin from `MD_ADMIN_USER`/`MD_ADMIN_PASSWORD` (or the config file),
+/// so a deployment configured entirely from the environment never shows the setup page.
+/// Does nothing once any user exists.
+async fn create_admin_from_overrides(pool: &SqlitePool, overrides: &overrides::Overrides) {
+    let Some((user, password)) = overrides.admin() else {
+        return;
+    };
+    if db::count_users(pool).await > 0 {
+        return;
+    }
+    let hash = match hash_password(password.to_string()).await {
+        Ok(hash) => hash,
+        Err((_, e)) => {
+            tracing::error!("Failed to hash the admin password: {}", e);
+            return;
+        }
+    };
+    if db::create_first_user(pool, user, &hash).await {
+        db::log_event(pool, None, "System", "Setup", &format!("Admin '{}' created from the environment", user)).await;
+    }
+}
+
+fn secret_keys() -> impl Iterator<Item = &'static str> {
+    api::all_fields().filter(|f| f.kind == api::FieldKind::Secret).map(|f| f.key)
+}
+
+/// Moves the connection settings earlier versions kept as flat `dashboard_settings` keys
+/// (plus the `service_instances` JSON) into the `services` table, encrypting any secret
+/// that was still stored in cleartext.
+async fn migrate_legacy_settings(pool: &SqlitePool, key: &secrets::SecretKey) -> Result<(), String> {
+    let settings = db::all_settings(pool).await;
+    let legacy: Vec<String> = api::all_setting_keys()
+        .chain(std::iter::once("service_instances".to_string()))
+        .filter(|name| settings.contains_key(name))
+        .collect();
+    if legacy.is_empty() {
+        return Ok(());
+    }
+
+    let mut config = load_config_from_db(pool, key).await?;
+    for name in api::all_setting_keys() {
+        let stored = settings.get(&name).map(String::as_str).unwrap_or_default();
+        config.services.set(&name, key.decrypt(stored).map_err(|e| format!("{}: {}", name, e))?);
+    }
+    if let Some(stored) = settings.get("service_instances").filter(|v| !v.is_empty()) {
+        let instances: Vec<api::ServiceInstance> = serde_json::from_str(stored)
+            .map_err(|e| format!("service_instances: {}", e))?;
+        config.instances = instances
+            .into_iter()
+            .map(|mut instance| {
+                for name in secret_keys() {
+                    let plaintext = key.decrypt(instance.settings.get(name))
+                        .map_err(|e| format!("service_instances.{}.{}: {}", instance.id, name, e))?;
+                    instance.settings.set(name, plaintext);
+                }
+                Ok(instance)
+            })
+            .collect::<Result<_, String>>()?;
+    }
+
+    save_config_to_db(pool, key, &config).await?;
+    db::delete_settings(pool, &legacy).await.map_err(|e| e.to_string())?;
+    db::log_event(pool, None, "System", "Migration", "Service connection settings moved to the services table").await;
+    Ok(())
+}
+
+/// `media-dashboard rotate-key`: re-encrypts every stored secret under a freshly generated key.
+/// The dashboard must be stopped while this runs, or it will keep writing with the old key.
+async fn rotate_secret_key(pool: &SqlitePool, current: &secrets::SecretKey) -> Result<(), String> {
+    let encoded = secrets::generate_key();
+    let next = secrets::SecretKey::from_base64(&encoded, secrets::KeySource::Env)?;
+    let config = load_config_from_db(pool, current).await?;
+    let settings = setting_rows(&next, &config);
+    let rows = service_rows(&next, &config);
+
+    match &current.source {
+        secrets::KeySource::File(path) => {
+            // Stage the new key first, so a failed commit never leaves the database without its key
+            let staged = path.with_extension("key.new");
+            secrets::write_key_file(&staged, &encoded)?;
+            db::save_config(pool, &settings, &rows).await.map_err(|e| e.to_string())?;
+            std::fs::rename(&staged, path)
+                .map_err(|e| format!("Secrets were re-encrypted, but moving {} into place failed: {}", staged.display(), e))?;
+            println!("Rotated secret key: {} service(s) re-encrypted, new key written to {}", rows.len(), path.display());
+        }
+        secrets::KeySource::Env | secrets::KeySource::Passphrase => {
+            db::save_config(pool, &settings, &rows).await.map_err(|e| e.to_string())?;
+            println!("Rotated secret key: {} service(s) re-encrypted.", rows.len());
+            println!("Set {}={} before starting the dashboard again.", secrets::KEY_ENV, encoded);
+        }
+    }
+    Ok(())
+}
+
+async fn load_config_from_db(pool: &SqlitePool, key: &secrets::SecretKey) -> Result<Config, String> {
+    let settings = db::all_settings(pool).await;
+    let mut config = Config {
+        poll_interval: settings.get("poll_interval")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(default_poll_interval),
+        session_lifetime_hours: settings.get("session_lifetime_hours")
+            .and_then(|v| v.parse().ok())
+            .filter(|&hours| auth::valid_session_lifetime(hours))
+            .unwrap_or_else(default_session_lifetime),
+        basic_auth_enabled: settings.get("basic_auth_enabled")
+            .is_some_and(|v| v == "true"),
+        oidc: auth::oidc::OidcSettings::default(),
+        retention: audit::retention::RetentionSettings::default(),
+        services: api::ServiceSettings::default(),
+        instances: Vec::new(),
+    };
+    for name in auth::oidc::SETTING_KEYS {
+        let stored = settings.get(name).map(String::as_str).unwrap_or_default();
+        config.oidc.set(name, key.decrypt(stored).map_err(|e| format!("{}: {}", name, e))?);
+    }
+    for name in audit::retention::SETTING_KEYS {
+        if let Some(stored) = settings.get(name) {
+            // A malformed stored value keeps the default, like the general settings above
+            let _ = config.retention.set(name, stored);
+        }
+    }
+
+    for row in db::list_services(pool).await.map_err(|e| e.to_string())? {
+        let instance = service_instance_from_row(key, row)?;
+        if instance.id == instance.kind {
+            for (name, value) in instance.settings.iter() {
+                config.services.set(name, value.to_string());
+            }
+        } else {
+            config.instances.push(instance);
+        }
+    }
+    Ok(config)
+}
+
+async fn save_config_to_db(pool: &SqlitePool, key: &secrets::SecretKey, config: &Config) -> Result<(), String> {
+    db::save_config(pool, &setting_rows(key, config), &service_rows(key, config)).await.map_err(|e| e.to_string())
+}
+
+/// The `dashboard_settings` rows of everything on `Config` besides the services, the
+/// OIDC client secret encrypted.
+fn setting_rows(key: &secrets::SecretKey, config: &Config) -> Vec<(String, String)> {
+    let mut rows = vec![
+        ("poll_interval".to_string(), config.poll_interval.to_string()),
+        ("session_lifetime_hours".to_string(), config.session_lifetime_hours.to_string()),
+        ("basic_auth_enabled".to_string(), config.basic_auth_enabled.to_string()),
+    ];
+    for name in auth::oidc::SETTING_KEYS {
+        let value = config.oidc.get(name);
+        let stored = if name == "oidc_client_secret" { key.encrypt(value) } else { value.to_string() };
+        rows.push((name.to_string(), stored));
+    }
+    for name in audit::retention::SETTING_KEYS {
+        rows.push((name.to_string(), config.retention.get(name)));
+    }
+    rows
+}
+
+/// Saves the running configuration. Settings pinned by overrides keep their previously
+/// stored values in the database, so they come back once the override is removed.
+async fn persist_config(state: &AppState, config: &Config) -> Result<(), String> {
+    let mut stored = config.clone();
+    if !state.overrides.is_empty() {
+        let previous = load_config_from_db(&state.db, &state.secrets).await?;
+        state.overrides.restore(&mut stored, &previous);
+    }
+    save_config_to_db(&state.db, &state.secrets, &stored).await
+}
+
+/// One `services` row per instance that has anything configured, secrets encrypted.
+fn service_rows(key: &secrets::SecretKey, config: &Config) -> Vec<db::ServiceRow> {
+    config
+        .all_instances()
+        .iter()
+        .filter_map(api::ServiceEntry::from_instance)
+        .enumerate()
+        .map(|(position, mut entry)| {
+            let _ = entry.map_secrets(|value| Ok::<_, ()>(Some(key.encrypt(value))));
+            db::ServiceRow {
+                auth_type: api::find(&entry.kind).map_or("api_key", |d| d.auth_type()).to_string(),
+                credentials: serde_json::to_string(&entry.credentials).unwrap_or_default(),
+                options: serde_json::to_string(&entry.options).unwrap_or_default(),
+                id: entry.id,
+                kind: entry.kind,
+                name: entry.name,
+                base_url: entry.base_url,
+                enabled: entry.enabled,
+                position: position as i64,
+            }
+        })
+        .collect()
+}
+
+/// Rebuilds an instance from its `services` row, decrypting its secrets.
+fn service_instance_from_row(key: &secrets::SecretKey, row: db::ServiceRow) -> Result<api::ServiceInstance, String> {
+    let parse = |json: &str| serde_json::from_str::<BTreeMap<String, String>>(json)
+        .map_err(|e| format!("services.{}: {}", row.id, e));
+    let mut entry = api::ServiceEntry {
+        credentials: parse(&row.credentials)?,
+        options: parse(&row.options)?,
+        id: row.id.clone(),
+        kind: row.kind,
+        name: row.name,
+        base_url: row.base_url,
+        enabled: row.enabled,
+    };
+    entry.map_secrets(|value| key.decrypt(value).map(Some))
+        .map_err(|e| format!("services.{}: {}", row.id, e))?;
+    entry.into_instance().map_err(|e| format!("services.{}", e))
 }
 
 // ===================== System & Logs Handlers =====================
 
-async fn get_system_logs() -> Result<String, AppError> {
-    fs::read_to_string("data/app.log")
-        .map_err(|e| (axum::http::StatusCode::NOT_FOUND, format!("Log file not found: {}", e)))
+#[derive(Deserialize)]
+struct SystemLogQuery {
+    /// The least severe level to show, e.g. `warn`.
+    level: Option<String>,
+    /// A module path prefix, e.g. `media_dashboard::poller`.
+    target: Option<String>,
+    /// Text to find in the message or other fields.
+    q: Option<String>,
+    /// The `next_cursor` of the previous page.
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+impl SystemLogQuery {
+    fn filter(&self) -> Result<logging::tail::LogFilter, AppError> {
+        fn given(value: &Option<String>) -> Option<&str> {
+            value.as_deref().filter(|v| !v.trim().is_empty())
+        }
+        logging::tail::LogFilter::new(given(&self.level), given(&self.target), given(&self.q))
+            .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))
+    }
+}
+
+/// One page of the JSON log, newest first, reading on into the rotated files.
+/// `next_cursor` fetches the page after it and is `null` on the last one; once the log has
+/// rotated it away, the cursor gets 410 Gone.
+async fn get_system_logs(Query(query): Query<SystemLogQuery>) -> Result<Json<serde_json::Value>, AppError> {
+    let filter = query.filter()?;
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(str::parse::<logging::tail::Cursor>)
+        .transpose()
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+    let limit = query.limit.unwrap_or(logging::tail::DEFAULT_PAGE_SIZE).clamp(1, logging::tail::MAX_PAGE_SIZE);
+    let (entries, next_cursor) = tokio::task::spawn_blocking(move || {
+        logging::tail::page(std::path::Path::new(logging::JSON_LOG_FILE), &filter, cursor, limit)
+    })
+    .await
+    .map_err(internal_err)?
+    .map_err(|e| match e {
+        logging::tail::PageError::Expired => {
+            (axum::http::StatusCode::GONE, "The log rotated past this cursor; start again from the newest entries".to_string())
+        }
+        logging::tail::PageError::Io(e) => internal_err(e),
+    })?;
+    Ok(Json(serde_json::json!({
+        "entries": entries,
+        "next_cursor": next_cursor.map(|cursor| cursor.to_string()),
+    })))
+}
+
+/// Streams new log entries matching the filters as `log` events while they are written.
+async fn follow_system_logs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SystemLogQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    let filter = query.filter()?;
+    let entries = BroadcastStream::new(state.log_feed.subscribe())
+        .filter_map(move |line| filter.matches(&line.ok()?))
+        .map(|entry| Event::default().event("log").json_data(entry));
+    Ok(Sse::new(entries).keep_alive(KeepAlive::default()))
 }
 
 // ===================== Transmission Control Handlers =====================
 
 async fn transmission_start_torrent(
     State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
     Path(id): Path<i64>,
 ) -> Result<axum::http::StatusCode, AppError> {
     let config = state.config.read().await;
-    api::transmission::start_torrent(&state.client, &config.transmission_url, &config.transmission_user, &config.transmission_pass, id)
-        .await.map_err(|e| internal_err(e))?;
-    db::log_event(&state.db, "Transmission", "Torrent Started", &format!("ID: {}", id)).await;
+    let torrent = torrent_info(&state, &config, id).await;
+    let name = audit_name(torrent.as_ref().and_then(|t| t["name"].as_str()), id);
+    let event = torrent_event("Torrent Started", format!("Torrent {} started", name), id, torrent.as_ref());
+    let attempt = format!("Failed to start torrent {}", name);
+    let result = rpc_outcome(api::transmission::start_torrent(&state.client, config.services.get("transmission_url"), config.services.get("transmission_user"), config.services.get("transmission_pass"), id).await);
+    audit_result(&state, &user, event, &attempt, result).await?;
     Ok(axum::http::StatusCode::OK)
 }
 
 async fn transmission_stop_torrent(
     State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<auth::Identity>,
     Path(id): Path<i64>,
 ) -> Result<axum::http::StatusCode, AppError> {
     let config = state.config.read().await;
-    api::transmission::stop_torrent(&state.client, &config.transmission_url, &config.transmission_user, &config.transmission_pass, id)
-        .await.map_err(|e| internal_err(e))?;
-    db::log_event(&state.db, "Transmission", "Torrent Stopped", &format!("ID: {}", id)).await;
+    let torrent = torrent_info(&state, &config, id).await;
+    let name = audit_name(torrent.as_ref().and_then(|t| t["name"].as_str()), id);
+    let event = torrent_event("Torrent Stopped", format!("Torrent {} stopped", name), id, torrent.as_ref());
+    let attempt = format!("Failed to stop torrent {}", name);
+    let result = rpc_outcome(api::transmission::stop_torrent(&state.client, config.services.get("transmission_url"), config.services.get("transmission_user"), config.services.get("transmission_pass"), id).await);
+    audit_result(&state, &user, event, &attempt, result).await?;
     Ok(axum::http::StatusCode::OK)
 }
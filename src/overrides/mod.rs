@@ -0,0 +1,230 @@
+use crate::{api, audit, auth};
+use crate::Config;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Names the config file when `--config` is not given.
+pub const CONFIG_ENV: &str = "MD_CONFIG";
+const ENV_PREFIX: &str = "MD_";
+
+/// Dashboard settings that live on `Config` itself rather than in `Config::services`.
+const GENERAL_KEYS: [&str; 3] = ["poll_interval", "session_lifetime_hours", "basic_auth_enabled"];
+/// Credentials of the first admin, created on start when no user exists yet.
+const ADMIN_KEYS: [&str; 2] = ["admin_user", "admin_password"];
+/// Read once on start rather than applied to `Config`: the listener, TLS, forward
+/// authentication and the application log.
+const STARTUP_KEYS: [&str; 15] = [
+    "listen_address",
+    "port",
+    "base_path",
+    "tls_cert",
+    "tls_key",
+    "tls_self_signed",
+    "proxy_auth_header",
+    "proxy_auth_groups_header",
+    "proxy_auth_trusted_proxies",
+    "proxy_auth_admin_groups",
+    "proxy_auth_operator_groups",
+    "proxy_auth_default_role",
+    "log_rotation",
+    "log_max_size_mb",
+    "log_keep_files",
+];
+
+/// Where an overridden setting came from.
+#[derive(Clone)]
+pub enum Source {
+    Env(String),
+    File(PathBuf),
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Env(name) => write!(f, "{}", name),
+            Source::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Settings pinned by environment variables or a declarative config file. They win over
+/// the values stored in the database, cannot be changed from the dashboard, and are never
+/// written back to it.
+#[derive(Default)]
+pub struct Overrides {
+    values: BTreeMap<String, (String, Source)>,
+}
+
+impl Overrides {
+    /// Reads the config file at `path` (TOML, or YAML by extension) if given, then
+    /// `MD_<KEY>` environment variables such as `MD_SONARR_URL`, which take precedence.
+    pub fn load(path: Option<&Path>) -> Result<Overrides, String> {
+        let mut overrides = Overrides::default();
+        if let Some(path) = path {
+            for (key, value) in read_file(path)? {
+                overrides.values.insert(key, (value, Source::File(path.to_path_buf())));
+            }
+        }
+        for key in known_keys() {
+            let name = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            // Compose files often declare a variable with an empty value to leave it unset
+            if let Ok(value) = std::env::var(&name).map(|v| v.trim().to_string()) {
+                if !value.is_empty() {
+                    overrides.values.insert(key, (value, Source::Env(name)));
+                }
+            }
+        }
+
+        for key in GENERAL_KEYS {
+            if let Some((value, source)) = overrides.values.get(key) {
+                let valid = match key {
+                    "basic_auth_enabled" => value.parse::<bool>().is_ok(),
+                    "session_lifetime_hours" => value.parse::<u64>().is_ok_and(auth::valid_session_lifetime),
+                    _ => value.parse::<u64>().is_ok_and(|n| n > 0),
+                };
+                if !valid {
+                    return Err(format!("{} ({}): invalid value '{}'", key, source, value));
+                }
+            }
+        }
+        for key in audit::retention::SETTING_KEYS {
+            if let Some((value, source)) = overrides.values.get(key) {
+                audit::retention::RetentionSettings::default()
+                    .set(key, value)
+                    .map_err(|e| format!("{} ({})", e, source))?;
+            }
+        }
+        Ok(overrides)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Every loaded setting, including the admin credentials and startup-only settings.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// A startup-only setting such as `port`, if set.
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|(value, _)| value.as_str())
+    }
+
+    /// Username and password of the admin to create on first start.
+    pub fn admin(&self) -> Option<(&str, &str)> {
+        let user = self.values.get("admin_user")?;
+        let password = self.values.get("admin_password")?;
+        Some((user.0.as_str(), password.0.as_str()))
+    }
+
+    /// Puts every overridden setting into `config`.
+    pub fn apply(&self, config: &mut Config) {
+        for (key, (value, _)) in self.settings() {
+            set(config, key, value.clone());
+        }
+    }
+
+    /// Undoes `apply` for persisting: every overridden setting takes its value from `stored`.
+    pub fn restore(&self, config: &mut Config, stored: &Config) {
+        for (key, _) in self.settings() {
+            set(config, key, get(stored, key));
+        }
+    }
+
+    /// Each overridden setting with the variable or file that sets it, for `/api/config`.
+    pub fn sources(&self) -> BTreeMap<String, String> {
+        self.settings().map(|(key, (_, source))| (key.clone(), source.to_string())).collect()
+    }
+
+    /// The overridden dashboard settings, without the admin credentials and startup-only settings.
+    fn settings(&self) -> impl Iterator<Item = (&String, &(String, Source))> {
+        self.values
+            .iter()
+            .filter(|(key, _)| !ADMIN_KEYS.contains(&key.as_str()) && !STARTUP_KEYS.contains(&key.as_str()))
+    }
+}
+
+/// Every settings key an override may set.
+fn known_keys() -> impl Iterator<Item = String> {
+    GENERAL_KEYS
+        .iter()
+        .chain(ADMIN_KEYS.iter())
+        .chain(STARTUP_KEYS.iter())
+        .chain(auth::oidc::SETTING_KEYS.iter())
+        .chain(audit::retention::SETTING_KEYS.iter())
+        .map(|key| key.to_string())
+        .chain(api::all_setting_keys())
+        .chain(api::SERVICES.iter().map(|s| s.enabled_key()))
+}
+
+fn set(config: &mut Config, key: &str, value: String) {
+    match key {
+        "poll_interval" => config.poll_interval = value.parse().unwrap_or(config.poll_interval),
+        "session_lifetime_hours" => config.session_lifetime_hours = value.parse().unwrap_or(config.session_lifetime_hours),
+        "basic_auth_enabled" => config.basic_auth_enabled = value == "true",
+        _ if key.starts_with("oidc_") => config.oidc.set(key, value),
+        // Validated by `Overrides::load`
+        _ if key.starts_with("retention_") => {
+            let _ = config.retention.set(key, &value);
+        }
+        _ => config.services.set(key, value),
+    }
+}
+
+fn get(config: &Config, key: &str) -> String {
+    match key {
+        "poll_interval" => config.poll_interval.to_string(),
+        "session_lifetime_hours" => config.session_lifetime_hours.to_string(),
+        "basic_auth_enabled" => config.basic_auth_enabled.to_string(),
+        _ if key.starts_with("oidc_") => config.oidc.get(key).to_string(),
+        _ if key.starts_with("retention_") => config.retention.get(key),
+        _ => config.services.get(key).to_string(),
+    }
+}
+
+/// Flattens the file into settings keys. Top-level values are keys as they are; a table
+/// names a service (or `admin`, `oidc`, `retention`, `log`) and its entries drop that prefix:
+///
+/// ```toml
+/// poll_interval = 60
+///
+/// [sonarr]
+/// url = "http://sonarr:8989"
+/// key = "..."
+/// ```
+fn read_file(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let yaml = path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml");
+    let document: serde_json::Value = if yaml {
+        serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+    } else {
+        let table: toml::Table = toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::to_value(table).map_err(|e| format!("{}: {}", path.display(), e))?
+    };
+    let serde_json::Value::Object(document) = document else {
+        return Err(format!("{}: expected a table of settings", path.display()));
+    };
+
+    let known: Vec<String> = known_keys().collect();
+    let mut settings = Vec::new();
+    for (name, value) in document {
+        let entries = match value {
+            serde_json::Value::Object(table) => table.into_iter().map(|(field, value)| (format!("{}_{}", name, field), value)).collect(),
+            value => vec![(name, value)],
+        };
+        for (key, value) in entries {
+            if !known.contains(&key) {
+                return Err(format!("{}: unknown setting '{}'", path.display(), key));
+            }
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                serde_json::Value::Number(n) => n.to_string(),
+                serde_json::Value::Bool(b) => b.to_string(),
+                _ => return Err(format!("{}: '{}' must be a string, number or boolean", path.display(), key)),
+            };
+            settings.push((key, value));
+        }
+    }
+    Ok(settings)
+}
@@ -0,0 +1,174 @@
+use crate::api::{self, ServiceInstance, ServiceStatus};
+use crate::events::DashboardEvent;
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 30;
+/// Guards the upstream services against a misconfigured, very short interval.
+const MIN_POLL_INTERVAL_SECS: u64 = 5;
+/// Torrent progress moves quickly, so it is checked more often than the full snapshot.
+const TORRENT_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+/// Probe history older than the longest uptime window is dropped.
+const CHECK_RETENTION_DAYS: i64 = 30;
+
+/// Latest results of the background refresh, shared by every open dashboard tab.
+#[derive(Clone, Default)]
+pub struct Snapshot {
+    /// When the snapshot was taken; `None` until the first refresh completes.
+    pub as_of: Option<DateTime<Utc>>,
+    pub statuses: Vec<ServiceStatus>,
+    pub calendar: serde_json::Value,
+    pub disk_space: serde_json::Value,
+}
+
+/// Starts the background task that keeps `AppState::snapshot` fresh.
+/// It refreshes immediately, then every `poll_interval` seconds or whenever
+/// `AppState::refresh` is notified (e.g. after the connection settings change).
+pub fn spawn(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            refresh(&state).await;
+
+            let interval = {
+                let config = state.config.read().await;
+                config.poll_interval.max(MIN_POLL_INTERVAL_SECS)
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(interval)) => {}
+                _ = state.refresh.notified() => {}
+            }
+        }
+    });
+}
+
+async fn refresh(state: &AppState) {
+    let instances = state.config.read().await.all_instances();
+    let client = &state.client;
+
+    let (statuses, calendar, disk_space) = tokio::join!(
+        api::poll_all(client, &instances),
+        fetch_calendar(client, &instances),
+        fetch_disk_space(client, &instances),
+    );
+
+    crate::db::record_checks(&state.db, &statuses).await;
+    crate::db::prune_checks(&state.db, Utc::now() - chrono::Duration::days(CHECK_RETENTION_DAYS)).await;
+
+    let mut snapshot = state.snapshot.write().await;
+    let changed = statuses_changed(&snapshot.statuses, &statuses);
+    *snapshot = Snapshot {
+        as_of: Some(Utc::now()),
+        statuses,
+        calendar,
+        disk_space,
+    };
+    if changed {
+        let _ = state.events.send(DashboardEvent::Status {
+            as_of: snapshot.as_of,
+            services: snapshot.statuses.clone(),
+        });
+    }
+}
+
+/// Latency jitters on every probe, so on its own it is not a change worth pushing.
+fn statuses_changed(old: &[ServiceStatus], new: &[ServiceStatus]) -> bool {
+    old.len() != new.len()
+        || old.iter().zip(new).any(|(a, b)| ServiceStatus { latency_ms: b.latency_ms, ..a.clone() } != *b)
+}
+
+/// Starts the task that pushes torrent progress deltas to `/api/events` subscribers.
+/// Transmission is only queried while at least one page is listening.
+pub fn spawn_torrent_watch(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut previous: HashMap<i64, serde_json::Value> = HashMap::new();
+        loop {
+            tokio::time::sleep(TORRENT_WATCH_INTERVAL).await;
+
+            if state.events.receiver_count() == 0 {
+                previous.clear();
+                continue;
+            }
+            let settings = state.config.read().await.services.clone();
+            let descriptor = &api::transmission::SERVICE;
+            if !descriptor.is_configured(&settings) {
+                continue;
+            }
+
+            let request = api::transmission::list_torrents(
+                &state.client,
+                settings.get("transmission_url"),
+                settings.get("transmission_user"),
+                settings.get("transmission_pass"),
+            );
+            let data = match tokio::time::timeout(descriptor.status_timeout(&settings), request).await {
+                Ok(Ok(data)) => data,
+                _ => continue,
+            };
+
+            let current: HashMap<i64, serde_json::Value> = data
+                .pointer("/arguments/torrents")
+                .and_then(|t| t.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|t| Some((t.get("id")?.as_i64()?, t.clone())))
+                .collect();
+
+            let changed: Vec<serde_json::Value> = current
+                .iter()
+                .filter(|(id, torrent)| previous.get(id) != Some(torrent))
+                .map(|(_, torrent)| torrent.clone())
+                .collect();
+            let removed: Vec<i64> = previous
+                .keys()
+                .filter(|id| !current.contains_key(id))
+                .copied()
+                .collect();
+
+            if !changed.is_empty() || !removed.is_empty() {
+                let _ = state.events.send(DashboardEvent::Torrents { changed, removed });
+            }
+            previous = current;
+        }
+    });
+}
+
+async fn fetch_calendar(client: &Client, instances: &[ServiceInstance]) -> serde_json::Value {
+    let now = Utc::now();
+    let end = now + chrono::Duration::days(7);
+    let start_str = now.format("%Y-%m-%d").to_string();
+    let end_str = end.format("%Y-%m-%d").to_string();
+
+    let (sonarr_cal, radarr_cal) = tokio::join!(
+        api::merge_instances(instances, "sonarr", |settings| {
+            api::sonarr::get_calendar(client, settings.get("sonarr_url"), settings.get("sonarr_key"), &start_str, &end_str)
+        }),
+        api::merge_instances(instances, "radarr", |settings| {
+            api::radarr::get_calendar(client, settings.get("radarr_url"), settings.get("radarr_key"), &start_str, &end_str)
+        }),
+    );
+
+    serde_json::json!({
+        "sonarr": sonarr_cal,
+        "radarr": radarr_cal
+    })
+}
+
+async fn fetch_disk_space(client: &Client, instances: &[ServiceInstance]) -> serde_json::Value {
+    let (sonarr_disk, radarr_disk) = tokio::join!(
+        api::merge_instances(instances, "sonarr", |settings| {
+            api::sonarr::get_disk_space(client, settings.get("sonarr_url"), settings.get("sonarr_key"))
+        }),
+        api::merge_instances(instances, "radarr", |settings| {
+            api::radarr::get_disk_space(client, settings.get("radarr_url"), settings.get("radarr_key"))
+        }),
+    );
+
+    serde_json::json!({
+        "sonarr_disk": sonarr_disk,
+        "radarr_disk": radarr_disk,
+    })
+}
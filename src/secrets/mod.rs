@@ -0,0 +1,147 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use std::path::{Path, PathBuf};
+
+/// Base64-encoded 32-byte key. Takes precedence over the key file.
+pub const KEY_ENV: &str = "MD_SECRET_KEY";
+/// Path of the key file; defaults to `secret.key` next to the database.
+pub const KEY_FILE_ENV: &str = "MD_SECRET_KEY_FILE";
+
+/// Prefix of values encrypted with this module, so legacy plaintext can be told apart.
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 24;
+
+/// Where the active key came from, which decides how a rotated key is handed back.
+pub enum KeySource {
+    Env,
+    File(PathBuf),
+    /// Derived from a user passphrase, e.g. for a config export.
+    Passphrase,
+}
+
+/// The key that encrypts service secrets stored in `dashboard_settings`.
+pub struct SecretKey {
+    cipher: XChaCha20Poly1305,
+    pub source: KeySource,
+}
+
+impl SecretKey {
+    /// Loads the key from `MD_SECRET_KEY`, or from the key file. If neither exists, a new
+    /// key file is generated so secrets are never written in cleartext.
+    pub fn load_or_create(database_path: &str) -> Result<SecretKey, String> {
+        if let Ok(encoded) = std::env::var(KEY_ENV) {
+            let cipher = cipher_from_base64(&encoded).map_err(|e| format!("{}: {}", KEY_ENV, e))?;
+            return Ok(SecretKey { cipher, source: KeySource::Env });
+        }
+
+        let path = key_file_path(database_path);
+        match std::fs::read_to_string(&path) {
+            Ok(encoded) => {
+                let cipher = cipher_from_base64(&encoded).map_err(|e| format!("{}: {}", path.display(), e))?;
+                Ok(SecretKey { cipher, source: KeySource::File(path) })
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let encoded = generate_key();
+                write_key_file(&path, &encoded)?;
+                tracing::warn!("Generated new secret key at {}. Back it up: without it the stored API keys cannot be decrypted.", path.display());
+                let cipher = cipher_from_base64(&encoded)?;
+                Ok(SecretKey { cipher, source: KeySource::File(path) })
+            }
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn from_base64(encoded: &str, source: KeySource) -> Result<SecretKey, String> {
+        Ok(SecretKey { cipher: cipher_from_base64(encoded)?, source })
+    }
+
+    /// Derives a key from a passphrase with Argon2id. `salt` must be stored alongside
+    /// whatever the key encrypts.
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<SecretKey, String> {
+        if passphrase.is_empty() {
+            return Err("Passphrase must not be empty".to_string());
+        }
+        let mut key = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| e.to_string())?;
+        let cipher = XChaCha20Poly1305::new_from_slice(&key).map_err(|e| e.to_string())?;
+        Ok(SecretKey { cipher, source: KeySource::Passphrase })
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        if plaintext.is_empty() {
+            return String::new();
+        }
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("XChaCha20-Poly1305 encryption does not fail for in-memory buffers");
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        format!("{}{}", PREFIX, STANDARD.encode(payload))
+    }
+
+    /// Decrypts a stored value. Values without the `enc:v1:` prefix are legacy plaintext
+    /// and are returned unchanged.
+    pub fn decrypt(&self, stored: &str) -> Result<String, String> {
+        let Some(encoded) = stored.strip_prefix(PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let payload = STANDARD.decode(encoded).map_err(|e| e.to_string())?;
+        if payload.len() < NONCE_LEN {
+            return Err("Encrypted value is truncated".to_string());
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Decryption failed; the secret key does not match this database".to_string())?;
+        String::from_utf8(plaintext).map_err(|e| e.to_string())
+    }
+}
+
+/// A random salt for `SecretKey::from_passphrase`, base64-encoded.
+pub fn generate_salt() -> String {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    STANDARD.encode(salt)
+}
+
+/// A fresh random key, base64-encoded as it is stored in the key file or env var.
+pub fn generate_key() -> String {
+    STANDARD.encode(XChaCha20Poly1305::generate_key(&mut OsRng))
+}
+
+pub fn key_file_path(database_path: &str) -> PathBuf {
+    match std::env::var(KEY_FILE_ENV) {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => Path::new(database_path)
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("secret.key"),
+    }
+}
+
+/// Writes a key file readable only by its owner.
+pub fn write_key_file(path: &Path, encoded: &str) -> Result<(), String> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    writeln!(file, "{}", encoded).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn cipher_from_base64(encoded: &str) -> Result<XChaCha20Poly1305, String> {
+    let key = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("key is not valid base64 ({})", e))?;
+    XChaCha20Poly1305::new_from_slice(&key).map_err(|_| "key must be exactly 32 bytes".to_string())
+}
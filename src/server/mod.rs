@@ -0,0 +1,142 @@
+use axum::extract::{ConnectInfo, Request};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::Router;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+pub mod tls;
+
+pub const DEFAULT_PORT: u16 = 7778;
+const UNIX_PREFIX: &str = "unix:";
+
+/// Where the dashboard accepts connections.
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl Listen {
+    /// `address` is an IPv4 or IPv6 address (bracketed or not), or `unix:<path>` for a
+    /// Unix socket, in which case `port` is ignored.
+    pub fn parse(address: Option<&str>, port: Option<&str>) -> Result<Listen, String> {
+        let address = address.unwrap_or("0.0.0.0").trim();
+        if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+            if path.is_empty() {
+                return Err("listen_address: unix: needs a socket path".to_string());
+            }
+            return Ok(Listen::Unix(PathBuf::from(path)));
+        }
+        let ip: IpAddr = address
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .map_err(|_| format!("listen_address: '{}' is not an IP address or unix:<path>", address))?;
+        let port = match port {
+            Some(port) => port.trim().parse().map_err(|_| format!("port: '{}' is not a port number", port))?,
+            None => DEFAULT_PORT,
+        };
+        Ok(Listen::Tcp(SocketAddr::new(ip, port)))
+    }
+}
+
+impl std::fmt::Display for Listen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Listen::Tcp(addr) => write!(f, "{}", addr),
+            Listen::Unix(path) => write!(f, "{}{}", UNIX_PREFIX, path.display()),
+        }
+    }
+}
+
+/// Normalizes the path the dashboard is served under to `/prefix` without a trailing
+/// slash, or an empty string when it is served at the root.
+pub fn normalize_base_path(raw: Option<&str>) -> Result<String, String> {
+    let trimmed = raw.unwrap_or_default().trim().trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    let valid = trimmed
+        .split('/')
+        .all(|segment| !segment.is_empty() && segment != "." && segment != ".." && segment.chars().all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c)));
+    if !valid {
+        return Err(format!("base_path: '{}' is not a valid URL path", raw.unwrap_or_default()));
+    }
+    Ok(format!("/{}", trimmed))
+}
+
+/// Mounts `app` under `base_path`. The prefix is stripped before `app` routes the
+/// request, and the bare prefix redirects to `prefix/` so the pages' relative links
+/// resolve inside it.
+pub fn mount(app: Router, base_path: &str) -> Router {
+    if base_path.is_empty() {
+        return app;
+    }
+    let base_path = base_path.to_string();
+    Router::new()
+        .fallback_service(app)
+        .layer(axum::middleware::from_fn(move |req: Request, next: Next| {
+            let base_path = base_path.clone();
+            async move { strip_base_path(&base_path, req, next).await }
+        }))
+}
+
+async fn strip_base_path(base_path: &str, mut req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    if path == base_path {
+        return Redirect::permanent(&format!("{}/", base_path)).into_response();
+    }
+    let Some(rest) = path.strip_prefix(base_path).filter(|rest| rest.starts_with('/')) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let rewritten = match req.uri().query() {
+        Some(query) => format!("{}?{}", rest, query),
+        None => rest.to_string(),
+    };
+    match rewritten.parse() {
+        Ok(uri) => *req.uri_mut() = uri,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    }
+    next.run(req).await
+}
+
+/// The address Unix socket peers are reported as.
+pub const UNIX_PEER: SocketAddr = SocketAddr::V4(std::net::SocketAddrV4::new(std::net::Ipv4Addr::LOCALHOST, 0));
+
+/// Serves `app` until the process exits, over HTTPS when `tls` is given (TCP only).
+/// Unix socket peers are reported as loopback clients, since whatever connects through
+/// the socket runs on this host.
+pub async fn serve(listen: &Listen, app: Router, tls: Option<tls::RustlsConfig>) -> std::io::Result<()> {
+    match listen {
+        Listen::Tcp(addr) => match tls {
+            Some(config) => {
+                axum_server::bind_rustls(*addr, config)
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await
+            }
+            None => {
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
+            }
+        },
+        Listen::Unix(path) => {
+            // A socket left behind by an unclean shutdown would make the bind fail
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(path)?;
+            let app = app.layer(axum::Extension(ConnectInfo(UNIX_PEER)));
+            loop {
+                let (stream, _) = listener.accept().await?;
+                let service = hyper_util::service::TowerToHyperService::new(app.clone());
+                tokio::spawn(async move {
+                    let io = hyper_util::rt::TokioIo::new(stream);
+                    if let Err(e) = hyper::server::conn::http1::Builder::new().serve_connection(io, service).await {
+                        tracing::debug!("Unix socket connection failed: {}", e);
+                    }
+                });
+            }
+        }
+    }
+}
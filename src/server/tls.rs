@@ -0,0 +1,86 @@
+pub use axum_server::tls_rustls::RustlsConfig;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often the certificate files are checked for changes, e.g. after a certbot renewal.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A PEM certificate chain and its private key.
+#[derive(Clone)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    /// The configured pair, or with `self_signed` a pair kept in `data_dir` that is generated
+    /// on first run. `None` serves plain HTTP.
+    pub fn resolve(cert: Option<&str>, key: Option<&str>, self_signed: bool, data_dir: &Path) -> Result<Option<TlsFiles>, String> {
+        match (cert, key) {
+            (Some(cert), Some(key)) => Ok(Some(TlsFiles { cert: cert.into(), key: key.into() })),
+            (Some(_), None) | (None, Some(_)) => Err("tls_cert and tls_key must be set together".to_string()),
+            (None, None) if self_signed => {
+                let files = TlsFiles { cert: data_dir.join("tls-cert.pem"), key: data_dir.join("tls-key.pem") };
+                if !files.cert.exists() || !files.key.exists() {
+                    generate_self_signed(&files)?;
+                }
+                Ok(Some(files))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub async fn load(&self) -> Result<RustlsConfig, String> {
+        // Both rustls backends end up in the dependency tree, so rustls cannot pick one itself
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        RustlsConfig::from_pem_file(&self.cert, &self.key)
+            .await
+            .map_err(|e| format!("Failed to load {} / {}: {}", self.cert.display(), self.key.display(), e))
+    }
+
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Some((modified(&self.cert)?, modified(&self.key)?))
+    }
+}
+
+/// Reloads `config` whenever either file changes. A pair that fails to load, e.g. because
+/// only one of the two files has been replaced so far, keeps the previous certificate.
+pub fn spawn_reload(config: RustlsConfig, files: TlsFiles) {
+    tokio::spawn(async move {
+        let mut loaded = files.modified();
+        let mut interval = tokio::time::interval(RELOAD_CHECK_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let current = files.modified();
+            if current.is_none() || current == loaded {
+                continue;
+            }
+            match config.reload_from_pem_file(&files.cert, &files.key).await {
+                Ok(()) => {
+                    loaded = current;
+                    tracing::info!("Reloaded TLS certificate from {}", files.cert.display());
+                }
+                Err(e) => tracing::warn!("Failed to reload TLS certificate, keeping the current one: {}", e),
+            }
+        }
+    });
+}
+
+fn generate_self_signed(files: &TlsFiles) -> Result<(), String> {
+    let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()];
+    if let Ok(hostname) = std::env::var("HOSTNAME") {
+        names.push(hostname);
+    }
+    let failed = |e: rcgen::Error| format!("Failed to generate a self-signed certificate: {}", e);
+    let mut params = rcgen::CertificateParams::new(names).map_err(failed)?;
+    params.distinguished_name.push(rcgen::DnType::CommonName, "Media Dashboard");
+    let key_pair = rcgen::KeyPair::generate().map_err(failed)?;
+    let cert = params.self_signed(&key_pair).map_err(failed)?;
+    crate::secrets::write_key_file(&files.key, &key_pair.serialize_pem())?;
+    std::fs::write(&files.cert, cert.pem())
+        .map_err(|e| format!("Failed to write {}: {}", files.cert.display(), e))?;
+    tracing::info!("Generated a self-signed TLS certificate at {}", files.cert.display());
+    Ok(())
+}
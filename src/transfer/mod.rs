@@ -0,0 +1,288 @@
+use crate::api::ServiceEntry;
+use crate::secrets::{self, SecretKey};
+use crate::{audit, auth, Config};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Version of the export document; bumped whenever its shape changes incompatibly.
+pub const FORMAT_VERSION: u32 = 1;
+const MASK: &str = "********";
+const OIDC_SECRET: &str = "oidc_client_secret";
+
+/// How service secrets are written into an export.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretsMode {
+    /// Left out; an import keeps the secrets already stored for the same instance.
+    Omit,
+    /// Encrypted with a key derived from a passphrase, whose salt travels in the document.
+    Encrypted,
+    Plaintext,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Yaml => "application/yaml",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct DashboardSettings {
+    pub poll_interval: u64,
+    pub session_lifetime_hours: u64,
+    pub basic_auth_enabled: bool,
+}
+
+/// A portable snapshot of every service connection and dashboard setting.
+#[derive(Serialize, Deserialize)]
+pub struct ConfigDocument {
+    pub version: u32,
+    #[serde(default)]
+    pub exported_at: Option<String>,
+    pub secrets: SecretsMode,
+    /// Base64 salt of the passphrase key when `secrets` is `encrypted`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    pub settings: DashboardSettings,
+    #[serde(default)]
+    pub services: Vec<ServiceEntry>,
+}
+
+/// One difference between the running configuration and an imported one.
+#[derive(Serialize)]
+pub struct Change {
+    /// Dotted path, e.g. `settings.poll_interval` or `services.radarr-4k.base_url`.
+    pub target: String,
+    /// `added`, `removed` or `modified`.
+    pub change: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<serde_json::Value>,
+}
+
+pub fn export(config: &Config, mode: SecretsMode, passphrase: Option<&str>) -> Result<ConfigDocument, String> {
+    let (key, salt) = match mode {
+        SecretsMode::Encrypted => {
+            let salt = secrets::generate_salt();
+            let key = passphrase_key(passphrase, &salt)?;
+            (Some(key), Some(salt))
+        }
+        _ => (None, None),
+    };
+
+    let mut services = entries(config);
+    for entry in &mut services {
+        let _ = entry.map_secrets(|value| {
+            Ok::<_, ()>(match (mode, &key) {
+                (SecretsMode::Plaintext, _) => Some(value.to_string()),
+                (SecretsMode::Encrypted, Some(key)) => Some(key.encrypt(value)),
+                _ => None,
+            })
+        });
+    }
+
+    Ok(ConfigDocument {
+        version: FORMAT_VERSION,
+        exported_at: Some(chrono::Utc::now().to_rfc3339()),
+        secrets: mode,
+        salt,
+        settings: DashboardSettings {
+            poll_interval: config.poll_interval,
+            session_lifetime_hours: config.session_lifetime_hours,
+            basic_auth_enabled: config.basic_auth_enabled,
+        },
+        services,
+    })
+}
+
+pub fn render(document: &ConfigDocument, format: Format) -> Result<String, String> {
+    match format {
+        Format::Json => serde_json::to_string_pretty(document).map_err(|e| e.to_string()),
+        Format::Yaml => serde_yaml::to_string(document).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn parse(body: &str, format: Format) -> Result<ConfigDocument, String> {
+    let document: ConfigDocument = match format {
+        Format::Json => serde_json::from_str(body).map_err(|e| e.to_string())?,
+        Format::Yaml => serde_yaml::from_str(body).map_err(|e| e.to_string())?,
+    };
+    if document.version > FORMAT_VERSION {
+        return Err(format!(
+            "Export format version {} is newer than this dashboard supports ({})",
+            document.version, FORMAT_VERSION
+        ));
+    }
+    Ok(document)
+}
+
+/// Builds the configuration an import would produce. With omitted secrets, each instance
+/// keeps the secrets `current` holds for the same id and kind.
+pub fn import(document: ConfigDocument, current: &Config, passphrase: Option<&str>) -> Result<Config, String> {
+    let key = match document.secrets {
+        SecretsMode::Encrypted => {
+            let salt = document.salt.as_deref().ok_or("Encrypted export is missing its salt")?;
+            Some(passphrase_key(passphrase, salt)?)
+        }
+        _ => None,
+    };
+    let existing = entries(current);
+
+    let mut next = Config {
+        poll_interval: document.settings.poll_interval,
+        session_lifetime_hours: document.settings.session_lifetime_hours,
+        basic_auth_enabled: document.settings.basic_auth_enabled,
+        // Single sign-on is tied to this deployment's URL, so it is not part of an export
+        oidc: current.oidc.clone(),
+        retention: current.retention.clone(),
+        services: Default::default(),
+        instances: Vec::new(),
+    };
+    for mut entry in document.services {
+        if let Some(key) = &key {
+            entry
+                .map_secrets(|value| key.decrypt(value).map(Some))
+                .map_err(|_| "Wrong passphrase, or the export was modified".to_string())?;
+        }
+        if document.secrets == SecretsMode::Omit {
+            if let Some(previous) = existing.iter().find(|e| e.id == entry.id && e.kind == entry.kind) {
+                for field in previous.secret_fields() {
+                    if let Some(value) = previous.credentials.get(field) {
+                        entry.credentials.entry(field.to_string()).or_insert_with(|| value.clone());
+                    }
+                }
+            }
+        }
+
+        let instance = entry.into_instance()?;
+        if instance.id == instance.kind {
+            for (name, value) in instance.settings.iter() {
+                next.services.set(name, value.to_string());
+            }
+        } else {
+            next.instances.push(instance);
+        }
+    }
+    Ok(next)
+}
+
+/// What changes between `current` and `next`. Secret values are masked.
+pub fn diff(current: &Config, next: &Config) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let settings = |config: &Config| -> BTreeMap<&'static str, serde_json::Value> {
+        let mut settings = BTreeMap::from([
+            ("poll_interval", config.poll_interval.into()),
+            ("session_lifetime_hours", config.session_lifetime_hours.into()),
+            ("basic_auth_enabled", config.basic_auth_enabled.into()),
+        ]);
+        for key in auth::oidc::SETTING_KEYS {
+            let shown = if key == OIDC_SECRET { MASK } else { config.oidc.get(key) };
+            settings.insert(key, shown.into());
+        }
+        let retention = serde_json::to_value(&config.retention).unwrap_or_default();
+        for key in audit::retention::SETTING_KEYS {
+            settings.insert(key, retention[key.trim_start_matches("retention_")].clone());
+        }
+        settings
+    };
+    let (before, after) = (settings(current), settings(next));
+    for (name, value) in &after {
+        // The client secret is compared unmasked so a changed secret still shows up
+        let changed = match *name {
+            OIDC_SECRET => current.oidc.client_secret != next.oidc.client_secret,
+            _ => before.get(name) != Some(value),
+        };
+        if changed {
+            changes.push(modified(format!("settings.{}", name), before.get(name).cloned(), Some(value.clone())));
+        }
+    }
+
+    let (before, after) = (entries(current), entries(next));
+    for entry in &before {
+        if !after.iter().any(|e| e.id == entry.id) {
+            changes.push(Change { target: format!("services.{}", entry.id), change: "removed", from: None, to: None });
+        }
+    }
+    for entry in &after {
+        let Some(previous) = before.iter().find(|e| e.id == entry.id) else {
+            changes.push(Change { target: format!("services.{}", entry.id), change: "added", from: None, to: None });
+            continue;
+        };
+        let fields = |e: &ServiceEntry| -> BTreeMap<String, serde_json::Value> {
+            let secret = e.secret_fields();
+            let mut fields = BTreeMap::from([
+                ("kind".to_string(), e.kind.clone().into()),
+                ("name".to_string(), e.name.clone().into()),
+                ("base_url".to_string(), e.base_url.clone().into()),
+                ("enabled".to_string(), e.enabled.into()),
+            ]);
+            for (field, value) in &e.credentials {
+                let shown = if secret.contains(&field.as_str()) { MASK } else { value.as_str() };
+                fields.insert(format!("credentials.{}", field), shown.into());
+            }
+            for (field, value) in &e.options {
+                fields.insert(format!("options.{}", field), value.clone().into());
+            }
+            fields
+        };
+        let (old, new) = (fields(previous), fields(entry));
+        let names: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        for name in names {
+            // Credentials are compared unmasked so a changed secret still shows up
+            let changed = match name.strip_prefix("credentials.") {
+                Some(field) => previous.credentials.get(field) != entry.credentials.get(field),
+                None => old.get(name) != new.get(name),
+            };
+            if changed {
+                changes.push(modified(format!("services.{}.{}", entry.id, name), old.get(name).cloned(), new.get(name).cloned()));
+            }
+        }
+    }
+    changes
+}
+
+fn modified(target: String, from: Option<serde_json::Value>, to: Option<serde_json::Value>) -> Change {
+    let change = match (&from, &to) {
+        (None, _) => "added",
+        (_, None) => "removed",
+        _ => "modified",
+    };
+    Change { target, change, from, to }
+}
+
+/// The before and after states of an audit entry for `changes`, keyed by target. A service
+/// that was added or removed has no value on one side and is marked as such.
+pub fn audit_states(changes: &[Change]) -> (serde_json::Value, serde_json::Value) {
+    let before = changes.iter().map(|c| (c.target.clone(), c.from.clone().unwrap_or_default())).collect();
+    let after = changes.iter().map(|c| (c.target.clone(), c.to.clone().unwrap_or_else(|| c.change.into()))).collect();
+    (serde_json::Value::Object(before), serde_json::Value::Object(after))
+}
+
+/// Every configured instance in export order, secrets in plaintext.
+fn entries(config: &Config) -> Vec<ServiceEntry> {
+    config.all_instances().iter().filter_map(ServiceEntry::from_instance).collect()
+}
+
+fn passphrase_key(passphrase: Option<&str>, salt: &str) -> Result<SecretKey, String> {
+    let salt = STANDARD.decode(salt).map_err(|e| format!("Invalid salt: {}", e))?;
+    SecretKey::from_passphrase(passphrase.unwrap_or_default(), &salt)
+}